@@ -0,0 +1,84 @@
+//! Writes `data/base_types.json` from the `base_items.json` of RePoE.
+//!
+//! `cargo run --example base_types -- <base_items.json> > data/base_types.json`
+//!
+//! Every base of an equipment class is listed by its full name, unreleased ones left out.
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Deserialize)]
+struct BaseItem {
+    name: String,
+    item_class: String,
+    release_state: String,
+}
+
+#[derive(Serialize, Default)]
+struct BaseClass {
+    slot: &'static str,
+    bases: BTreeSet<String>,
+}
+
+/// Class name used by item filters and the slot of an item class of RePoE.
+fn class_of(item_class: &str) -> Option<(&'static str, &'static str)> {
+    Some(match item_class {
+        "Amulet" => ("Amulets", "Amulet"),
+        "Ring" => ("Rings", "Ring"),
+        "Belt" => ("Belts", "Belt"),
+        "Gloves" => ("Gloves", "Gloves"),
+        "Boots" => ("Boots", "Boots"),
+        "Helmet" => ("Helmets", "Helmet"),
+        "Body Armour" => ("Body Armours", "Body"),
+        "Shield" => ("Shields", "Weapon1HOrShield"),
+        "Quiver" => ("Quivers", "Useless"),
+        "Claw" => ("Claws", "Weapon1HOrShield"),
+        "Dagger" => ("Daggers", "Weapon1HOrShield"),
+        "Rune Dagger" => ("Rune Daggers", "Weapon1HOrShield"),
+        "Wand" => ("Wands", "Weapon1HOrShield"),
+        "Sceptre" => ("Sceptres", "Weapon1HOrShield"),
+        "One Hand Axe" => ("One Hand Axes", "Weapon1HOrShield"),
+        "One Hand Mace" => ("One Hand Maces", "Weapon1HOrShield"),
+        "One Hand Sword" => ("One Hand Swords", "Weapon1HOrShield"),
+        "Thrusting One Hand Sword" => ("Thrusting One Hand Swords", "Weapon1HOrShield"),
+        "Bow" => ("Bows", "Weapon2H"),
+        "Staff" => ("Staves", "Weapon2H"),
+        "Warstaff" => ("Warstaves", "Weapon2H"),
+        "Two Hand Axe" => ("Two Hand Axes", "Weapon2H"),
+        "Two Hand Mace" => ("Two Hand Maces", "Weapon2H"),
+        "Two Hand Sword" => ("Two Hand Swords", "Weapon2H"),
+        _ => return None,
+    })
+}
+
+fn main() {
+    let path = match std::env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: base_types <base_items.json>");
+            std::process::exit(2);
+        }
+    };
+    let items: BTreeMap<String, BaseItem> = match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+    {
+        Ok(items) => items,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            std::process::exit(2);
+        }
+    };
+
+    let mut classes: BTreeMap<&str, BaseClass> = BTreeMap::new();
+    for item in items.into_values() {
+        if item.release_state == "unreleased" {
+            continue;
+        }
+        if let Some((class, slot)) = class_of(&item.item_class) {
+            let class = classes.entry(class).or_default();
+            class.slot = slot;
+            class.bases.insert(item.name);
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&classes).unwrap());
+}
@@ -0,0 +1,82 @@
+//! Runs every generator on a synthetic stash and prints what they made of it.
+//!
+//! `cargo run --example simulate -- <stash.json> [chaos|regal|influenced|<recipe.json>] [--json]`
+//!
+//! The stash is a `StashSpec`, or a fixture from `tests/fixtures/simulate` holding one under
+//! `stash`. Exits with 1 if any set breaks the recipe.
+use helper::{simulate, Generator, RecipeMode, RecipeRule, StashSpec};
+
+fn main() {
+    let mut json = false;
+    let mut paths = Vec::new();
+    for arg in std::env::args().skip(1) {
+        if arg == "--json" {
+            json = true;
+        } else {
+            paths.push(arg);
+        }
+    }
+    if paths.is_empty() || paths.len() > 2 {
+        eprintln!("usage: simulate <stash.json> [chaos|regal|influenced|<recipe.json>] [--json]");
+        std::process::exit(2);
+    }
+
+    let stash = match read_stash(&paths[0]) {
+        Ok(stash) => stash,
+        Err(e) => {
+            eprintln!("{}: {}", paths[0], e);
+            std::process::exit(2);
+        }
+    };
+    let rule = match paths.get(1).map(String::as_str) {
+        None | Some("chaos") => RecipeMode::Chaos.rule().clone(),
+        Some("regal") => RecipeMode::Regal.rule().clone(),
+        Some("influenced") => RecipeMode::Influenced.rule().clone(),
+        Some(path) => match read_rule(path) {
+            Ok(rule) => rule,
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                std::process::exit(2);
+            }
+        },
+    };
+
+    let items = stash.items();
+    let mut broken = false;
+    for generator in Generator::ALL.iter() {
+        let report = simulate(&items, &rule, *generator);
+        broken |= !report.violations.is_empty();
+        if json {
+            let line = serde_json::json!({ "generator": generator, "report": report });
+            println!("{}", line);
+            continue;
+        }
+        println!(
+            "{:?}: {} sets ({} unidentified)",
+            generator, report.sets, report.unidentified_sets
+        );
+        let mut leftovers: Vec<_> = report.leftovers.iter().collect();
+        leftovers.sort_by(|(a, _), (b, _)| a.as_ref().cmp(b.as_ref()));
+        for (i_type, count) in leftovers {
+            println!("  left: {} x{}", i_type.as_ref(), count);
+        }
+        for violation in report.violations.iter() {
+            println!("  violation: {:?}", violation);
+        }
+    }
+    if broken {
+        std::process::exit(1);
+    }
+}
+
+fn read_rule(path: &str) -> Result<RecipeRule, Box<dyn std::error::Error>> {
+    Ok(RecipeRule::from_json(&std::fs::read_to_string(path)?)?)
+}
+
+fn read_stash(path: &str) -> Result<StashSpec, Box<dyn std::error::Error>> {
+    let mut value: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    if let Some(stash) = value.get_mut("stash") {
+        value = stash.take();
+    }
+    Ok(serde_json::from_value(value)?)
+}
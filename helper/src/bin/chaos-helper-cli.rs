@@ -0,0 +1,537 @@
+//! Prints the stash status and the recipe sets without the overlay.
+//!
+//! The account is read from the file the overlay saves, `~/chaos_helper.info`.
+use helper::{
+    earnings_by_league, earnings_by_session, AccountData, ApiServer, Currency, Earnings,
+    FileSource, HelperError, HelperSession, ItemType, Ledger, LogWatcher, LootFilter, Realm,
+    RecipeMode, ResponseFromNetwork, DEFAULT_LOG_POLL_INTERVAL,
+};
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+const SAVE_FILE_NAME: &str = "chaos_helper.info";
+const LEDGER_FILE_NAME: &str = "chaos_helper_sales.jsonl";
+
+const USAGE: &str =
+    "usage: chaos-helper-cli <status|sets|refresh|leagues|serve|history|filter> [options]
+
+options:
+    --json               print JSON instead of text
+    --config <path>      account file, ~/chaos_helper.info by default
+    --stash-file <path>  read the stash from a saved response instead of the site
+    --recipe <name>      Chaos, Regal or Influenced, Chaos by default
+    --realm <name>       realm to list the leagues of, the one of the account by default
+    --timeout <seconds>  how long to wait for the stash, 60 by default
+    --port <port>        port `serve` listens on at localhost, 8787 by default
+    --client-log <path>  `Client.txt` of the game, `serve` refreshes the stash in hideouts and towns
+    --ledger <path>      sold sets `serve` records and `history` sums up,
+                         ~/chaos_helper_sales.jsonl by default
+    --base <path>        loot filter `filter` adds the recipe block to
+    --output <path>      where `filter` writes the filter, the base is overwritten by default
+    --target-sets <n>    sets worth of items a slot needs before `filter` shrinks it, 4 by default
+    --hide-saturated     make `filter` hide the items of full slots instead of shrinking them";
+
+/// Item types in the order the overlay shows them.
+const TYPES: [ItemType; 9] = [
+    ItemType::Weapon1HOrShield,
+    ItemType::Weapon2H,
+    ItemType::Body,
+    ItemType::Helmet,
+    ItemType::Gloves,
+    ItemType::Belt,
+    ItemType::Boots,
+    ItemType::Ring,
+    ItemType::Amulet,
+];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Status,
+    Sets,
+    Refresh,
+    Leagues,
+    Serve,
+    History,
+    Filter,
+}
+
+struct Options {
+    command: Command,
+    json: bool,
+    config: Option<PathBuf>,
+    stash_file: Option<PathBuf>,
+    mode: RecipeMode,
+    realm: Option<Realm>,
+    timeout: Duration,
+    port: u16,
+    client_log: Option<PathBuf>,
+    ledger: Option<PathBuf>,
+    base: Option<PathBuf>,
+    output: Option<PathBuf>,
+    target_sets: usize,
+    hide_saturated: bool,
+}
+
+fn main() {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n\n{}", message, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = run(&options) {
+        eprintln!("error: {}", e);
+        if let Some(e) = e.downcast_ref::<HelperError>() {
+            eprintln!("{}", e.english_suggestion());
+        }
+        std::process::exit(1);
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let command = match args.next().as_deref() {
+        Some("status") => Command::Status,
+        Some("sets") => Command::Sets,
+        Some("refresh") => Command::Refresh,
+        Some("leagues") => Command::Leagues,
+        Some("serve") => Command::Serve,
+        Some("history") => Command::History,
+        Some("filter") => Command::Filter,
+        Some(other) => return Err(format!("unknown command '{}'", other)),
+        None => return Err("no command given".to_owned()),
+    };
+    let mut options = Options {
+        command,
+        json: false,
+        config: None,
+        stash_file: None,
+        mode: RecipeMode::default(),
+        realm: None,
+        timeout: Duration::from_secs(60),
+        port: 8787,
+        client_log: None,
+        ledger: None,
+        base: None,
+        output: None,
+        target_sets: 4,
+        hide_saturated: false,
+    };
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "--json" => options.json = true,
+            "--config" => options.config = Some(value()?.into()),
+            "--stash-file" => options.stash_file = Some(value()?.into()),
+            "--recipe" => {
+                let name = value()?;
+                options.mode = *RecipeMode::ALL
+                    .iter()
+                    .find(|mode| mode.as_ref().eq_ignore_ascii_case(&name))
+                    .ok_or_else(|| format!("unknown recipe '{}'", name))?;
+            }
+            "--realm" => {
+                let name = value()?;
+                options.realm = Some(
+                    Realm::ALL
+                        .iter()
+                        .find(|realm| format!("{:?}", realm).eq_ignore_ascii_case(&name))
+                        .cloned()
+                        .ok_or_else(|| format!("unknown realm '{}'", name))?,
+                );
+            }
+            "--timeout" => {
+                let seconds = value()?;
+                let seconds = seconds
+                    .parse()
+                    .map_err(|_| format!("invalid timeout '{}'", seconds))?;
+                options.timeout = Duration::from_secs(seconds);
+            }
+            "--port" => {
+                let port = value()?;
+                options.port = port
+                    .parse()
+                    .map_err(|_| format!("invalid port '{}'", port))?;
+            }
+            "--client-log" => options.client_log = Some(value()?.into()),
+            "--ledger" => options.ledger = Some(value()?.into()),
+            "--base" => options.base = Some(value()?.into()),
+            "--output" => options.output = Some(value()?.into()),
+            "--target-sets" => {
+                let sets = value()?;
+                options.target_sets = sets
+                    .parse()
+                    .map_err(|_| format!("invalid number of sets '{}'", sets))?;
+            }
+            "--hide-saturated" => options.hide_saturated = true,
+            _ => return Err(format!("unknown option '{}'", arg)),
+        }
+    }
+    if command == Command::Filter && options.base.is_none() {
+        return Err("filter needs --base".to_owned());
+    }
+    Ok(options)
+}
+
+fn load_account(options: &Options) -> Result<AccountData, Box<dyn std::error::Error>> {
+    let path = match &options.config {
+        Some(path) => path.clone(),
+        None => dirs::home_dir()
+            .ok_or("can't find the home directory")?
+            .join(SAVE_FILE_NAME),
+    };
+    // 저장 파일에 계정 정보 말고 다른 설정도 있지만 무시한다.
+    let file = std::fs::File::open(&path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+}
+
+fn load_ledger(options: &Options) -> Result<Ledger, Box<dyn std::error::Error>> {
+    Ok(Ledger::new(match &options.ledger {
+        Some(path) => path.clone(),
+        None => dirs::home_dir()
+            .ok_or("can't find the home directory")?
+            .join(LEDGER_FILE_NAME),
+    }))
+}
+
+fn run(options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    if options.command == Command::Leagues {
+        let realm = match &options.realm {
+            Some(realm) => realm.clone(),
+            None => load_account(options)
+                .map(|account| account.realm)
+                .unwrap_or_default(),
+        };
+        let leagues = helper::get_league_list(&realm)?;
+        if options.json {
+            println!("{}", json!(leagues));
+        } else {
+            for league in leagues {
+                println!("{}", league);
+            }
+        }
+        return Ok(());
+    }
+    if options.command == Command::History {
+        let (sales, skipped) = load_ledger(options)?.read()?;
+        if skipped > 0 {
+            eprintln!("Skipped {} lines of the ledger which aren't sales", skipped);
+        }
+        print_history(&sales, options.json);
+        return Ok(());
+    }
+
+    let account = match (load_account(options), &options.stash_file) {
+        (Ok(account), _) => account,
+        // 파일에서 읽을 때는 계정 정보가 없어도 된다.
+        (Err(_), Some(_)) => AccountData::default(),
+        (Err(e), None) => return Err(e),
+    };
+    let session = match &options.stash_file {
+        Some(path) => HelperSession::with_source(account, FileSource::new(path)),
+        None => HelperSession::new(account),
+    };
+    if options.command == Command::Serve {
+        let session = Arc::new(session);
+        load_ledger(options)?.record(&session)?;
+        let server = ApiServer::start(session.clone(), options.port)?;
+        eprintln!("Listening on http://{}", server.local_addr());
+        let _watcher = match &options.client_log {
+            Some(path) => Some(LogWatcher::start(path, session, DEFAULT_LOG_POLL_INTERVAL)?),
+            None => None,
+        };
+        loop {
+            std::thread::park();
+        }
+    }
+
+    let status = session.refresh_stash_status(options.mode, options.timeout)?;
+    if let ResponseFromNetwork::StashStatus { generation: 0, .. } = status {
+        return Err("the stash was not fetched before the timeout".into());
+    }
+
+    match options.command {
+        Command::Status => print_status(&status, options.json),
+        Command::Refresh => print_refresh(&status, options.json),
+        Command::Sets => {
+            let sets = futures::executor::block_on(session.sets(options.mode))?;
+            print_sets(&sets, options.json);
+        }
+        Command::Filter => {
+            if let ResponseFromNetwork::StashStatus { recipe_set, .. } = &status {
+                // parse_args에서 확인했다.
+                let base = options.base.clone().unwrap();
+                let filter = LootFilter {
+                    output: options.output.clone().unwrap_or_else(|| base.clone()),
+                    base,
+                    mode: options.mode,
+                    target_sets: options.target_sets,
+                    hide_saturated: options.hide_saturated,
+                };
+                filter.write(recipe_set)?;
+                print_slot_counts(
+                    &helper::slot_counts(recipe_set, filter.mode, filter.target_sets),
+                    options.json,
+                );
+            }
+        }
+        Command::Leagues | Command::Serve | Command::History => unreachable!(),
+    }
+    Ok(())
+}
+
+fn print_status(status: &ResponseFromNetwork, as_json: bool) {
+    if let ResponseFromNetwork::StashStatus {
+        recipe_set,
+        sets,
+        unidentified_sets,
+        mode,
+        influenced,
+        unclassified,
+        cooldown,
+        age,
+        ..
+    } = status
+    {
+        let counts = |item_type: &ItemType| {
+            recipe_set
+                .get(item_type)
+                .map(|(chaos, regal)| (chaos.len(), regal.len()))
+                .unwrap_or((0, 0))
+        };
+
+        if as_json {
+            let types: serde_json::Map<_, _> = TYPES
+                .iter()
+                .map(|item_type| {
+                    let (chaos, regal) = counts(item_type);
+                    (
+                        item_type.as_ref().to_owned(),
+                        json!({ "chaos": chaos, "regal": regal }),
+                    )
+                })
+                .collect();
+            let influenced: Vec<_> = influenced
+                .iter()
+                .map(|summary| {
+                    let items: serde_json::Map<_, _> = summary
+                        .items
+                        .iter()
+                        .map(|(item_type, count)| (item_type.as_ref().to_owned(), json!(count)))
+                        .collect();
+                    json!({
+                        "influence": summary.influence,
+                        "sets": summary.sets,
+                        "items": items,
+                    })
+                })
+                .collect();
+            let unclassified: Vec<_> = unclassified.iter().map(item_json).collect();
+            println!(
+                "{}",
+                json!({
+                    "mode": mode,
+                    "types": types,
+                    "sets": sets,
+                    "unidentified_sets": unidentified_sets,
+                    "influenced": influenced,
+                    "unclassified": unclassified,
+                    "cooldown": cooldown.map(|cooldown| cooldown.as_secs()),
+                    "age": age.map(|age| age.as_secs()),
+                })
+            );
+            return;
+        }
+
+        println!("--- Type: (ilvl<75, ilvl>=75) ---");
+        for item_type in TYPES.iter() {
+            let (chaos, regal) = counts(item_type);
+            println!("{}: ({}, {})", item_type.as_ref(), chaos, regal);
+        }
+        println!("Total {}: {}", mode, sets);
+        println!("Unidentified (x2): {}", unidentified_sets);
+        for summary in influenced.iter() {
+            println!(
+                "{}: {} ({})",
+                summary.influence.as_ref(),
+                summary.sets,
+                summary.items.values().sum::<usize>()
+            );
+        }
+        for item in unclassified.iter() {
+            println!(
+                "Unclassified: {} (tab {}, {}, {})",
+                item.base_type, item.tab_idx, item.x, item.y
+            );
+        }
+        if let Some(cooldown) = cooldown {
+            println!("Cooldown: {}s", cooldown.as_secs() + 1);
+        }
+    }
+}
+
+fn print_refresh(status: &ResponseFromNetwork, as_json: bool) {
+    if let ResponseFromNetwork::StashStatus {
+        sets,
+        mode,
+        cooldown,
+        generation,
+        ..
+    } = status
+    {
+        if as_json {
+            println!(
+                "{}",
+                json!({
+                    "generation": generation,
+                    "mode": mode,
+                    "sets": sets,
+                    "cooldown": cooldown.map(|cooldown| cooldown.as_secs()),
+                })
+            );
+        } else {
+            print!("Fetched the stash: {} {} sets", sets, mode);
+            match cooldown {
+                Some(cooldown) => println!(", cooldown {}s", cooldown.as_secs() + 1),
+                None => println!(),
+            }
+        }
+    }
+}
+
+fn print_slot_counts(counts: &[helper::SlotCount], as_json: bool) {
+    if as_json {
+        println!("{}", json!(counts));
+        return;
+    }
+    for count in counts {
+        print!(
+            "{:<18} {}/{}",
+            count.slot.as_ref(),
+            count.have,
+            count.wanted
+        );
+        match count.is_saturated() {
+            true => println!(" full"),
+            false => println!(),
+        }
+    }
+}
+
+fn print_sets(sets: &[ResponseFromNetwork], as_json: bool) {
+    let mut all = Vec::new();
+    for (idx, set) in sets.iter().enumerate() {
+        let (items, tabs, tab_names, layout) = match set {
+            ResponseFromNetwork::ChaosRecipe {
+                items,
+                tabs,
+                tab_names,
+                layout,
+                ..
+            } => (items, tabs, tab_names, layout),
+            _ => continue,
+        };
+        if as_json {
+            let items: Vec<_> = items
+                .iter()
+                .enumerate()
+                .map(|(pos, item)| {
+                    let mut value = item_json(item);
+                    value["inventory"] = json!(layout.as_ref().map(|layout| layout[pos]));
+                    value
+                })
+                .collect();
+            all.push(json!({
+                "tabs": tabs,
+                "tab_names": tab_names,
+                "fits_inventory": layout.is_some(),
+                "items": items,
+            }));
+            continue;
+        }
+
+        println!("Set {} (tabs {})", idx + 1, tab_names.join(", "));
+        if layout.is_none() {
+            println!("  doesn't fit the inventory at once");
+        }
+        for (pos, item) in items.iter().enumerate() {
+            let tab = tabs.iter().position(|tab| *tab == item.tab_idx);
+            print!(
+                "  {:<16} {:<24} ilvl {:>2}  tab {} ({}, {})",
+                item.item_type().as_ref(),
+                item.base_type,
+                item.ilvl(),
+                tab.map_or("?", |tab| tab_names[tab].as_str()),
+                item.x,
+                item.y
+            );
+            match layout {
+                Some(layout) => println!("  -> ({}, {})", layout[pos].0, layout[pos].1),
+                None => println!(),
+            }
+        }
+    }
+    if as_json {
+        println!("{}", json!(all));
+    }
+}
+
+fn print_history(sales: &[helper::Sale], as_json: bool) {
+    let leagues = earnings_by_league(sales);
+    let sessions = earnings_by_session(sales);
+    if as_json {
+        let sessions: Vec<_> = sessions
+            .iter()
+            .map(|(started, earnings)| json!({ "started": started, "earnings": earnings }))
+            .collect();
+        println!(
+            "{}",
+            json!({ "total": Earnings::of(sales), "leagues": leagues, "sessions": sessions })
+        );
+        return;
+    }
+
+    if sales.is_empty() {
+        println!("No sold sets yet");
+        return;
+    }
+    let line = |earnings: &Earnings| {
+        let orbs: Vec<_> = earnings
+            .orbs
+            .iter()
+            .map(|(currency, amount)| format!("{} {}", amount, currency.as_ref()))
+            .collect();
+        format!(
+            "{} sets ({} unidentified), {}, {:.1}h, {:.1} chaos/h",
+            earnings.sets,
+            earnings.unidentified_sets,
+            orbs.join(", "),
+            earnings.hours,
+            earnings.per_hour(Currency::Chaos)
+        )
+    };
+    println!("Total: {}", line(&Earnings::of(sales)));
+    for (league, earnings) in leagues.iter() {
+        println!("{}: {}", league, line(earnings));
+    }
+    for (idx, earnings) in sessions.values().enumerate() {
+        println!("Session {}: {}", idx + 1, line(earnings));
+    }
+}
+
+fn item_json(item: &helper::Item) -> serde_json::Value {
+    json!({
+        "type": item.item_type(),
+        "base_type": item.base_type,
+        "ilvl": item.ilvl(),
+        "identified": item.identified,
+        "influences": item.influences(),
+        "tab": item.tab_idx,
+        "x": item.x,
+        "y": item.y,
+        "w": item.w,
+        "h": item.h,
+    })
+}
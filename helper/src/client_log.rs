@@ -0,0 +1,162 @@
+use crate::{HelperError, HelperSession, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::thread::{spawn, JoinHandle};
+use std::time::Duration;
+
+/// How often `LogWatcher` looks for new lines.
+pub const DEFAULT_LOG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Towns of every act, as the English client names them.
+const TOWNS: [&str; 11] = [
+    "Lioneye's Watch",
+    "The Forest Encampment",
+    "The Sarn Encampment",
+    "Highgate",
+    "Overseer's Tower",
+    "The Bridge Encampment",
+    "Oriath Docks",
+    "Oriath",
+    "Karui Shores",
+    "The Rogue Harbour",
+    "Kingsmarch",
+];
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AreaKind {
+    Hideout,
+    Town,
+    /// maps and every other area
+    Other,
+}
+
+/// Something in `Client.txt` the helper cares about.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum LogEvent {
+    AreaEntered { area: String, kind: AreaKind },
+}
+
+impl LogEvent {
+    /// Whether the player is likely to open the stash soon, so it should be fetched now.
+    pub fn wants_refresh(&self) -> bool {
+        match self {
+            LogEvent::AreaEntered { kind, .. } => *kind != AreaKind::Other,
+        }
+    }
+
+    /// Reads a line like `2021/01/18 20:13:36 1234 bad [INFO Client 42] : You have entered Oriath.`
+    ///
+    /// Only messages of the client itself are read, so chat can't fake them.
+    pub fn parse(line: &str) -> Option<Self> {
+        let idx = line.find("] : ")?;
+        let message = line[idx + 4..].trim_end();
+        let area = match message.strip_prefix("You have entered ") {
+            Some(area) => area.strip_suffix('.')?,
+            // 카카오 클라이언트
+            None => message.strip_suffix("에 진입했습니다.")?,
+        };
+        let kind = if area.ends_with("Hideout") || area.contains("은신처") {
+            AreaKind::Hideout
+        } else if TOWNS.contains(&area) {
+            AreaKind::Town
+        } else {
+            AreaKind::Other
+        };
+        Some(LogEvent::AreaEntered {
+            area: area.to_owned(),
+            kind,
+        })
+    }
+}
+
+/// Follows `Client.txt` of the game, like `tail -f`.
+#[derive(Debug)]
+pub struct ClientLog {
+    path: PathBuf,
+    pos: u64,
+    /// the end of the file, when the game hasn't finished writing the line yet
+    partial: Vec<u8>,
+}
+
+impl ClientLog {
+    /// Starts at the end of the file, since only what happens from now on matters.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let pos = std::fs::metadata(path.as_ref())?.len();
+        Ok(Self {
+            path: path.as_ref().to_owned(),
+            pos,
+            partial: Vec::new(),
+        })
+    }
+
+    /// Events in the lines written since the last call.
+    pub fn poll(&mut self) -> Result<Vec<LogEvent>> {
+        // 게임이 쓰는 동안에도 읽을 수 있도록 매번 새로 연다.
+        let mut file = File::open(&self.path)?;
+        if file.metadata()?.len() < self.pos {
+            // 로그 파일이 지워지고 새로 만들어졌다.
+            self.pos = 0;
+            self.partial.clear();
+        }
+        file.seek(SeekFrom::Start(self.pos))?;
+        let mut data = std::mem::take(&mut self.partial);
+        self.pos += file.read_to_end(&mut data)? as u64;
+
+        let complete = match data.iter().rposition(|byte| *byte == b'\n') {
+            Some(idx) => idx + 1,
+            None => 0,
+        };
+        self.partial = data.split_off(complete);
+        Ok(String::from_utf8_lossy(&data)
+            .lines()
+            .filter_map(LogEvent::parse)
+            .collect())
+    }
+}
+
+/// Refreshes a session in the background whenever the player enters a hideout or a town.
+///
+/// The fetches go through `HelperSession::schedule_refresh`, so they wait for the rate
+/// limits. The watcher stops when it is dropped.
+#[derive(Debug)]
+pub struct LogWatcher {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl LogWatcher {
+    pub fn start(
+        path: impl AsRef<Path>,
+        session: Arc<HelperSession>,
+        interval: Duration,
+    ) -> Result<Self> {
+        let mut log = ClientLog::open(path)?;
+        let (stop, stop_recv) = mpsc::channel();
+        let thread = spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stop_recv.recv_timeout(interval) {
+                // 게임이 파일을 잠깐 잠가도 다음에 다시 읽으면 된다.
+                let events = log.poll().unwrap_or_default();
+                if events.iter().any(LogEvent::wants_refresh) {
+                    if let Err(HelperError::Disconnected) = session.schedule_refresh() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for LogWatcher {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt::{self, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub type Result<T, E = HelperError> = std::result::Result<T, E>;
+
+#[derive(Debug, Clone)]
+pub enum HelperError {
+    /// POESESSID is expired or belongs to another account (401, 403)
+    InvalidSession,
+    /// too many requests (429)
+    RateLimited {
+        retry_after: Option<Duration>,
+    },
+    UnknownLeague(String),
+    TabOutOfRange(usize),
+    /// a tab chosen by name is not in the tab list anymore
+    TabNotFound(String),
+    /// the profile is private and no cookie was given (403)
+    PrivateProfile,
+    MalformedJson(Arc<serde_json::Error>),
+    /// any other error the site answered with
+    Api {
+        status: u16,
+        message: String,
+    },
+    Network(Arc<reqwest::Error>),
+    Io(Arc<std::io::Error>),
+    /// the network thread is not running
+    Disconnected,
+}
+
+impl HelperError {
+    /// What the user can do about the error.
+    pub fn suggestion(&self) -> &'static str {
+        match self {
+            HelperError::InvalidSession => {
+                "POESESSID 쿠키가 만료되었거나 다른 계정의 것입니다. 쿠키를 다시 붙여넣어 주세요."
+            }
+            HelperError::RateLimited { .. } => {
+                "요청이 너무 많습니다. 잠시 후에 다시 시도해 주세요."
+            }
+            HelperError::UnknownLeague(_) => "리그를 다시 선택해 주세요.",
+            HelperError::TabOutOfRange(_) | HelperError::TabNotFound(_) => {
+                "창고 탭 목록을 다시 불러와서 탭을 선택해 주세요."
+            }
+            HelperError::PrivateProfile => {
+                "프로필이 비공개입니다. POESESSID 쿠키를 입력하거나 프로필을 공개로 바꿔 주세요."
+            }
+            HelperError::MalformedJson(_) | HelperError::Api { .. } => {
+                "사이트의 응답을 이해할 수 없습니다. 잠시 후에 다시 시도해 주세요."
+            }
+            HelperError::Network(_) => "인터넷 연결을 확인해 주세요.",
+            HelperError::Io(_) => "파일 경로를 확인해 주세요.",
+            HelperError::Disconnected => "프로그램을 다시 실행해 주세요.",
+        }
+    }
+
+    /// `suggestion` in English, for the command line tool.
+    pub fn english_suggestion(&self) -> &'static str {
+        match self {
+            HelperError::InvalidSession => {
+                "The POESESSID cookie has expired or belongs to another account. Paste it again."
+            }
+            HelperError::RateLimited { .. } => "Too many requests. Try again in a while.",
+            HelperError::UnknownLeague(_) => "Choose the league again.",
+            HelperError::TabOutOfRange(_) | HelperError::TabNotFound(_) => {
+                "Load the list of stash tabs again and choose the tabs."
+            }
+            HelperError::PrivateProfile => {
+                "The profile is private. Enter the POESESSID cookie or make the profile public."
+            }
+            HelperError::MalformedJson(_) | HelperError::Api { .. } => {
+                "The answer of the site couldn't be understood. Try again in a while."
+            }
+            HelperError::Network(_) => "Check the internet connection.",
+            HelperError::Io(_) => "Check the file path.",
+            HelperError::Disconnected => "Run the program again.",
+        }
+    }
+
+    /// Maps an error response of the stash API.
+    ///
+    /// The site answers with `{"error": {"code": .., "message": ..}}`, sometimes even with
+    /// a successful status.
+    pub(crate) fn from_response(
+        status: u16,
+        body: &str,
+        retry_after: Option<Duration>,
+        league: &str,
+        tab_idx: usize,
+        has_cookie: bool,
+    ) -> Self {
+        #[derive(Deserialize)]
+        struct ErrorBody {
+            error: ErrorDetail,
+        }
+        #[derive(Deserialize)]
+        struct ErrorDetail {
+            #[serde(default)]
+            message: String,
+        }
+
+        let message = serde_json::from_str::<ErrorBody>(body)
+            .map(|body| body.error.message)
+            .unwrap_or_default();
+        let lower_message = message.to_lowercase();
+        match status {
+            429 => HelperError::RateLimited { retry_after },
+            401 => HelperError::InvalidSession,
+            403 if has_cookie => HelperError::InvalidSession,
+            403 => HelperError::PrivateProfile,
+            _ if lower_message.contains("league") => HelperError::UnknownLeague(league.to_owned()),
+            _ if lower_message.contains("tab") => HelperError::TabOutOfRange(tab_idx),
+            404 => HelperError::UnknownLeague(league.to_owned()),
+            _ => HelperError::Api { status, message },
+        }
+    }
+}
+
+impl fmt::Display for HelperError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            HelperError::InvalidSession => f.write_str("The session id is invalid or expired"),
+            HelperError::RateLimited {
+                retry_after: Some(after),
+            } => write!(f, "Rate limited for {} seconds", after.as_secs()),
+            HelperError::RateLimited { retry_after: None } => f.write_str("Rate limited"),
+            HelperError::UnknownLeague(league) => write!(f, "Unknown league '{}'", league),
+            HelperError::TabOutOfRange(idx) => write!(f, "Stash tab {} is out of range", idx),
+            HelperError::TabNotFound(name) => write!(f, "Can't find the stash tab '{}'", name),
+            HelperError::PrivateProfile => f.write_str("The profile is private"),
+            HelperError::MalformedJson(e) => write!(f, "Malformed response: {}", e),
+            HelperError::Api { status, message } => write!(f, "Error {}: {}", status, message),
+            HelperError::Network(e) => write!(f, "Network error: {}", e),
+            HelperError::Io(e) => write!(f, "IO error: {}", e),
+            HelperError::Disconnected => f.write_str("The network thread is not running"),
+        }
+    }
+}
+
+impl Error for HelperError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            HelperError::MalformedJson(e) => Some(e.as_ref()),
+            HelperError::Network(e) => Some(e.as_ref()),
+            HelperError::Io(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for HelperError {
+    fn from(e: serde_json::Error) -> Self {
+        HelperError::MalformedJson(Arc::new(e))
+    }
+}
+
+impl From<reqwest::Error> for HelperError {
+    fn from(e: reqwest::Error) -> Self {
+        HelperError::Network(Arc::new(e))
+    }
+}
+
+impl From<std::io::Error> for HelperError {
+    fn from(e: std::io::Error) -> Self {
+        HelperError::Io(Arc::new(e))
+    }
+}
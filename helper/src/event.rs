@@ -0,0 +1,62 @@
+use crate::{ChaosRecipeSet, HelperError, Item, ItemType, RecipeMode};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Something that changed in a session, sent to every subscriber.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "event")]
+pub enum StashEvent {
+    /// the number of items of a type changed, or a new subscriber wants the current counts
+    CountsChanged {
+        /// (ilvl<75, ilvl>=75) items of each type
+        counts: HashMap<ItemType, (usize, usize)>,
+        mode: RecipeMode,
+        sets: usize,
+        generation: u64,
+    },
+    /// a fetch brought more sets than there were before
+    SetAvailable { mode: RecipeMode, sets: usize },
+    /// some items of the set being picked up left the stash, but not all of them
+    SetPartlyPicked {
+        mode: RecipeMode,
+        picked: Vec<Item>,
+        /// items still in the stash
+        left: Vec<Item>,
+    },
+    /// every item of the set being picked up left the stash tabs the session reads, which
+    /// is also what moving them to another tab looks like
+    SetConsumed {
+        mode: RecipeMode,
+        items: Vec<Item>,
+        /// league of the stash the set was built from
+        league: String,
+        /// sets left after it
+        remaining: usize,
+    },
+    /// the site didn't accept the account, so no fetch will work until it is changed
+    AuthError {
+        error: String,
+        suggestion: &'static str,
+    },
+}
+
+impl StashEvent {
+    /// `AuthError` if `error` means the cookie or the profile settings are wrong.
+    pub(crate) fn auth_error(error: &HelperError) -> Option<Self> {
+        match error {
+            HelperError::InvalidSession | HelperError::PrivateProfile => {
+                Some(StashEvent::AuthError {
+                    error: error.to_string(),
+                    suggestion: error.suggestion(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn counts(map: &ChaosRecipeSet) -> HashMap<ItemType, (usize, usize)> {
+    map.iter()
+        .map(|(i_type, (chaos, regal))| (*i_type, (chaos.len(), regal.len())))
+        .collect()
+}
@@ -0,0 +1,295 @@
+use crate::filter_file::FilterFile;
+use crate::item_class::classes_of;
+use crate::{
+    ChaosRecipeSet, HelperSession, Identification, IlvlBand, Influence, InfluenceRule, ItemType,
+    RecipeMode, RecipeRule, ResponseFromNetwork, Result, SlotItems, SlotRule, StashEvent,
+};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::thread::spawn;
+
+/// First line of the block the helper writes into a filter.
+pub const BLOCK_BEGIN: &str = "# chaos-helper begin";
+/// Last line of the block the helper writes into a filter.
+pub const BLOCK_END: &str = "# chaos-helper end";
+
+/// How many items of a slot the stash has, against how many are wanted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+pub struct SlotCount {
+    pub slot: ItemType,
+    pub have: usize,
+    pub wanted: usize,
+}
+
+impl SlotCount {
+    pub fn is_saturated(&self) -> bool {
+        self.have >= self.wanted
+    }
+}
+
+/// Items of each slot of `mode` in `map`, in the order of the recipe file. `target_sets`
+/// sets worth of items are wanted, so twice as many rings.
+pub fn slot_counts(map: &ChaosRecipeSet, mode: RecipeMode, target_sets: usize) -> Vec<SlotCount> {
+    let rule = mode.rule();
+    let mut counts: Vec<SlotCount> = Vec::new();
+    for items in slot_items(rule) {
+        for slot in items.types.iter() {
+            let wanted = target_sets * items.count;
+            match counts.iter_mut().find(|count| count.slot == *slot) {
+                Some(count) => count.wanted = count.wanted.max(wanted),
+                None => counts.push(SlotCount {
+                    slot: *slot,
+                    have: map
+                        .get(slot)
+                        .map(|(chaos_list, regal_list)| {
+                            chaos_list
+                                .iter()
+                                .chain(regal_list.iter())
+                                .filter(|item| rule.accepts(item))
+                                .count()
+                        })
+                        .unwrap_or(0),
+                    wanted,
+                }),
+            }
+        }
+    }
+    counts
+}
+
+/// Items in `band` which `rule` accepts, of any slot.
+fn band_count(map: &ChaosRecipeSet, rule: &RecipeRule, band: IlvlBand) -> usize {
+    map.values()
+        .flat_map(|(chaos_list, regal_list)| chaos_list.iter().chain(regal_list.iter()))
+        .filter(|item| rule.accepts(item) && band.contains(item.ilvl))
+        .count()
+}
+
+/// Conditions matching the items of `rule` in `band`.
+fn conditions(rule: &RecipeRule, band: IlvlBand) -> Vec<String> {
+    let mut conditions = vec![format!("Rarity {:?}", rule.rarity)];
+    conditions.push(format!("ItemLevel >= {}", band.min));
+    if let Some(max) = band.max {
+        conditions.push(format!("ItemLevel <= {}", max));
+    }
+    match rule.identified {
+        Identification::Any => {}
+        Identification::Identified => conditions.push("Identified True".to_owned()),
+        Identification::Unidentified => conditions.push("Identified False".to_owned()),
+    }
+    match rule.influence {
+        InfluenceRule::Any => {}
+        InfluenceRule::None => conditions.push("HasInfluence None".to_owned()),
+        InfluenceRule::Same => {
+            let names: Vec<_> = Influence::ALL.iter().map(|i| i.as_ref()).collect();
+            conditions.push(format!("HasInfluence {}", names.join(" ")));
+        }
+    }
+    conditions
+}
+
+/// Item levels from which filters like NeverSink's show bases for crafting.
+const CRAFTING_ILVL: usize = 86;
+
+/// Conditions leaving the items filters show no matter what, like 6-links, influenced bases
+/// and bases for crafting, to the rules of the base filter.
+fn exclusions(rule: &RecipeRule) -> Vec<String> {
+    let mut exclusions = vec!["LinkedSockets < 6".to_owned()];
+    if !matches!(rule.ilvl.max, Some(max) if max < CRAFTING_ILVL) {
+        exclusions.push(format!("ItemLevel < {}", CRAFTING_ILVL));
+    }
+    // 영향력 레시피는 영향받은 아이템만 쓴다.
+    if rule.influence == InfluenceRule::Any {
+        exclusions.push("HasInfluence None".to_owned());
+    }
+    exclusions
+}
+
+fn push_block(lines: &mut Vec<String>, header: String, conditions: &[String], style: &[&str]) {
+    lines.push(header);
+    for condition in conditions.iter() {
+        lines.push(format!("    {}", condition));
+    }
+    for action in style.iter() {
+        lines.push(format!("    {}", action));
+    }
+    lines.push(String::new());
+}
+
+fn slot_items(rule: &RecipeRule) -> Vec<&SlotItems> {
+    rule.slots
+        .iter()
+        .flat_map(|slot| match slot {
+            SlotRule::Items(items) => vec![items],
+            SlotRule::OneOf { one_of } => one_of.iter().flatten().collect(),
+        })
+        .collect()
+}
+
+/// A loot filter kept up to date with the stash.
+///
+/// The filter is `base` with a block in front of it which shows the recipe items the stash
+/// is short of, and hides or shrinks the ones it has enough of.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LootFilter {
+    /// filter to add the block to, like one of NeverSink's
+    pub base: PathBuf,
+    /// where to write the filter, usually next to `base` in the settings folder of the game
+    pub output: PathBuf,
+    #[serde(default)]
+    pub mode: RecipeMode,
+    /// sets worth of items a slot needs before it is saturated
+    #[serde(default = "default_target_sets")]
+    pub target_sets: usize,
+    /// hide items of saturated slots instead of showing them small
+    #[serde(default)]
+    pub hide_saturated: bool,
+}
+
+fn default_target_sets() -> usize {
+    4
+}
+
+impl LootFilter {
+    /// The block for the stash in `map`, from `BLOCK_BEGIN` to `BLOCK_END`.
+    ///
+    /// Items of saturated slots in an item level band the recipe requires stay shown while
+    /// the stash has too few of them for the wanted sets. Items the base filter shows no
+    /// matter what are never hidden or shrunk.
+    pub fn recipe_block(&self, map: &ChaosRecipeSet) -> String {
+        let rule = self.mode.rule();
+        let counts = slot_counts(map, self.mode, self.target_sets);
+        let mut lines = vec![
+            BLOCK_BEGIN.to_owned(),
+            format!(
+                "# {} recipe, {} sets wanted. Written by Chaos Helper, changes here are lost.",
+                self.mode, self.target_sets
+            ),
+        ];
+        let saturated: Vec<_> = counts
+            .iter()
+            .filter(|count| count.is_saturated())
+            .flat_map(|count| classes_of(count.slot))
+            .map(|class| format!("\"{}\"", class))
+            .collect();
+        for requirement in rule.require.iter() {
+            let have = band_count(map, rule, requirement.ilvl);
+            let wanted = self.target_sets * requirement.count;
+            if have >= wanted || saturated.is_empty() {
+                continue;
+            }
+            let band = match requirement.ilvl.max {
+                Some(max) => format!("{}-{}", requirement.ilvl.min, max),
+                None => format!("{}+", requirement.ilvl.min),
+            };
+            let mut conditions = conditions(rule, requirement.ilvl);
+            conditions.push(format!("Class {}", saturated.join(" ")));
+            // 슬롯 블록보다 앞에 있어야 숨겨지지 않는다.
+            push_block(
+                &mut lines,
+                format!("Show # ItemLevel {} {}/{}", band, have, wanted),
+                &conditions,
+                &WANTED_STYLE,
+            );
+        }
+        for count in counts {
+            let classes: Vec<_> = classes_of(count.slot)
+                .iter()
+                .map(|class| format!("\"{}\"", class))
+                .collect();
+            let (keyword, style) = if count.is_saturated() {
+                let keyword = if self.hide_saturated { "Hide" } else { "Show" };
+                (keyword, &SATURATED_STYLE[..])
+            } else if count.have == 0 {
+                ("Show", &MISSING_STYLE[..])
+            } else {
+                ("Show", &WANTED_STYLE[..])
+            };
+            let mut conditions = conditions(rule, rule.ilvl);
+            conditions.push(format!("Class {}", classes.join(" ")));
+            if count.is_saturated() {
+                conditions.extend(exclusions(rule));
+            }
+            push_block(
+                &mut lines,
+                format!(
+                    "{} # {} {}/{}",
+                    keyword,
+                    count.slot.as_ref(),
+                    count.have,
+                    count.wanted
+                ),
+                &conditions,
+                style,
+            );
+        }
+        lines.push(BLOCK_END.to_owned());
+        lines.join("\n") + "\n"
+    }
+
+    /// Writes `base` with the block for `map` to `output`.
+    pub fn write(&self, map: &ChaosRecipeSet) -> Result<()> {
+        let base = std::fs::read_to_string(&self.base)?;
+        std::fs::write(&self.output, inject_block(&base, &self.recipe_block(map)))?;
+        Ok(())
+    }
+
+    /// Writes the filter again whenever the counts of `session` change, until the session
+    /// is dropped. The game only reads it when the filter is reloaded.
+    pub fn keep_updated(&self, session: &HelperSession) -> Result<()> {
+        // 기본 필터를 읽을 수 없으면 바로 알려준다.
+        std::fs::metadata(&self.base)?;
+        let events = session.subscribe()?;
+        let session = session.status_requester();
+        let filter = self.clone();
+        spawn(move || {
+            for event in events {
+                match event {
+                    StashEvent::CountsChanged { generation, .. } if generation > 0 => {}
+                    _ => continue,
+                }
+                let status = match session.status(filter.mode) {
+                    Ok(status) => status,
+                    Err(_) => break,
+                };
+                if let ResponseFromNetwork::StashStatus { recipe_set, .. } = status {
+                    // 쓰지 못했으면 다음에 개수가 바뀔 때 다시 쓴다.
+                    filter.write(&recipe_set).ok();
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+/// Shown big, with a beam and a minimap icon.
+const MISSING_STYLE: [&str; 6] = [
+    "SetFontSize 45",
+    "SetTextColor 255 255 255 255",
+    "SetBorderColor 255 40 40 255",
+    "SetBackgroundColor 120 0 0 240",
+    "PlayEffect Red",
+    "MinimapIcon 0 Red Diamond",
+];
+
+const WANTED_STYLE: [&str; 5] = [
+    "SetFontSize 38",
+    "SetTextColor 255 255 255 255",
+    "SetBorderColor 255 200 0 255",
+    "PlayEffect Yellow Temp",
+    "MinimapIcon 2 Yellow Circle",
+];
+
+const SATURATED_STYLE: [&str; 3] = [
+    "SetFontSize 18",
+    "SetTextColor 150 150 150 180",
+    "SetBackgroundColor 0 0 0 120",
+];
+
+/// Puts `block` in place of the block written before, or in front of `filter` if there is
+/// none. Filters use the first block an item matches, so it has to come first.
+pub fn inject_block(filter: &str, block: &str) -> String {
+    let mut filter = FilterFile::parse(filter);
+    filter.set_helper_block(block);
+    filter.to_string()
+}
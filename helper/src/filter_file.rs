@@ -0,0 +1,346 @@
+use crate::{Result, BLOCK_BEGIN, BLOCK_END};
+use std::fmt::{self, Formatter};
+use std::ops::Range;
+use std::path::Path;
+
+const BOM: char = '\u{feff}';
+
+/// Keywords of the lines that change how a dropped item looks or sounds. Every other
+/// keyword is a condition.
+const ACTIONS: [&str; 15] = [
+    "SetFontSize",
+    "SetTextColor",
+    "SetBorderColor",
+    "SetBackgroundColor",
+    "PlayAlertSound",
+    "PlayAlertSoundPositional",
+    "CustomAlertSound",
+    "CustomAlertSoundOptional",
+    "DisableDropSound",
+    "EnableDropSound",
+    "DisableDropSoundIfAlertSound",
+    "EnableDropSoundIfAlertSound",
+    "MinimapIcon",
+    "PlayEffect",
+    "Continue",
+];
+
+/// What a block does with the items it matches.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Visibility {
+    Show,
+    Hide,
+    /// shown only while the highlight key is held
+    Minimal,
+}
+
+impl Visibility {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "Show" => Some(Visibility::Show),
+            "Hide" => Some(Visibility::Hide),
+            "Minimal" => Some(Visibility::Minimal),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    /// the last line of a file which doesn't end with a newline
+    None,
+}
+
+impl LineEnding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::None => "",
+        }
+    }
+}
+
+/// A condition like `ItemLevel >= 60` or an action like `SetFontSize 45`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FilterRule {
+    pub keyword: String,
+    /// `==`, `>=` and the like, if there is one
+    pub operator: Option<String>,
+    /// without the quotes
+    pub values: Vec<String>,
+    pub comment: Option<String>,
+}
+
+impl FilterRule {
+    pub fn is_action(&self) -> bool {
+        ACTIONS.contains(&self.keyword.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum LineKind {
+    Blank,
+    /// the text after `#`
+    Comment(String),
+    /// `Show`, `Hide` or `Minimal`, and the comment after it
+    Block {
+        visibility: Visibility,
+        comment: Option<String>,
+    },
+    Condition(FilterRule),
+    Action(FilterRule),
+}
+
+/// A line of a filter, kept as it was written.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FilterLine {
+    text: String,
+    ending: LineEnding,
+    kind: LineKind,
+}
+
+impl FilterLine {
+    fn parse(text: &str, ending: LineEnding) -> Self {
+        let trimmed = text.trim();
+        let kind = if trimmed.is_empty() {
+            LineKind::Blank
+        } else if let Some(comment) = trimmed.strip_prefix('#') {
+            LineKind::Comment(comment.trim().to_owned())
+        } else {
+            let (mut words, comment) = split_words(trimmed);
+            let keyword = words.remove(0);
+            match Visibility::from_keyword(&keyword) {
+                Some(visibility) => LineKind::Block {
+                    visibility,
+                    comment,
+                },
+                None => {
+                    let rule = rule(keyword, words, comment);
+                    match rule.is_action() {
+                        true => LineKind::Action(rule),
+                        false => LineKind::Condition(rule),
+                    }
+                }
+            }
+        };
+        Self {
+            text: text.to_owned(),
+            ending,
+            kind,
+        }
+    }
+
+    /// The line without its line ending.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn ending(&self) -> LineEnding {
+        self.ending
+    }
+
+    pub fn kind(&self) -> &LineKind {
+        &self.kind
+    }
+}
+
+/// Splits the words of a line, keeping quoted words together, from the comment after `#`.
+fn split_words(text: &str) -> (Vec<String>, Option<String>) {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // `""`도 값 하나로 센다.
+    let mut in_word = false;
+    let mut quoted = false;
+    for (idx, c) in text.char_indices() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                in_word = true;
+            }
+            '#' if !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                }
+                return (words, Some(text[idx + 1..].trim().to_owned()));
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    (words, None)
+}
+
+fn rule(keyword: String, mut values: Vec<String>, comment: Option<String>) -> FilterRule {
+    let is_operator = |c: char| matches!(c, '=' | '!' | '<' | '>');
+    let mut operator = None;
+    if let Some(first) = values.first_mut() {
+        let len = first.len() - first.trim_start_matches(is_operator).len();
+        if len > 0 {
+            // `>=60`처럼 붙여 쓰기도 한다.
+            operator = Some(first[..len].to_owned());
+            first.replace_range(..len, "");
+            if first.is_empty() {
+                values.remove(0);
+            }
+        }
+    }
+    FilterRule {
+        keyword,
+        operator,
+        values,
+        comment,
+    }
+}
+
+/// A `Show`, `Hide` or `Minimal` block of a filter.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FilterBlock<'a> {
+    pub visibility: Visibility,
+    pub comment: Option<&'a str>,
+    pub conditions: Vec<&'a FilterRule>,
+    pub actions: Vec<&'a FilterRule>,
+    /// indices of its lines, from the `Show` or `Hide` line
+    pub lines: Range<usize>,
+}
+
+/// A loot filter, parsed line by line so it is written back byte for byte.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FilterFile {
+    /// whether the file starts with a UTF-8 BOM, like the ones FilterBlade makes
+    bom: bool,
+    lines: Vec<FilterLine>,
+}
+
+impl FilterFile {
+    /// Any text is a filter. Lines the game wouldn't understand are read as conditions.
+    pub fn parse(text: &str) -> Self {
+        let (bom, mut rest) = match text.strip_prefix(BOM) {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        let mut lines = Vec::new();
+        while !rest.is_empty() {
+            let (line, ending, next) = match rest.find('\n') {
+                Some(idx) => match rest[..idx].strip_suffix('\r') {
+                    Some(line) => (line, LineEnding::CrLf, &rest[idx + 1..]),
+                    None => (&rest[..idx], LineEnding::Lf, &rest[idx + 1..]),
+                },
+                None => (rest, LineEnding::None, ""),
+            };
+            lines.push(FilterLine::parse(line, ending));
+            rest = next;
+        }
+        Self { bom, lines }
+    }
+
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::parse(&std::fs::read_to_string(path)?))
+    }
+
+    pub fn lines(&self) -> &[FilterLine] {
+        &self.lines
+    }
+
+    /// The line ending of the first line, which new lines are written with.
+    pub fn line_ending(&self) -> LineEnding {
+        self.lines
+            .iter()
+            .map(|line| line.ending)
+            .find(|ending| *ending != LineEnding::None)
+            .unwrap_or(LineEnding::Lf)
+    }
+
+    /// Every block, in the order the game tries them.
+    ///
+    /// A block goes on until a blank line or the next block.
+    pub fn blocks(&self) -> Vec<FilterBlock<'_>> {
+        let mut blocks = Vec::new();
+        for (start, line) in self.lines.iter().enumerate() {
+            let (visibility, comment) = match &line.kind {
+                LineKind::Block {
+                    visibility,
+                    comment,
+                } => (*visibility, comment.as_deref()),
+                _ => continue,
+            };
+            let mut block = FilterBlock {
+                visibility,
+                comment,
+                conditions: Vec::new(),
+                actions: Vec::new(),
+                lines: start..start + 1,
+            };
+            for line in self.lines[start + 1..].iter() {
+                match &line.kind {
+                    LineKind::Condition(rule) => block.conditions.push(rule),
+                    LineKind::Action(rule) => block.actions.push(rule),
+                    // 주석 처리된 줄도 블록에 속한다.
+                    LineKind::Comment(_) => {}
+                    _ => break,
+                }
+                block.lines.end += 1;
+            }
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    /// Indices of the lines from `BLOCK_BEGIN` to `BLOCK_END`, if the helper wrote them.
+    pub fn helper_block(&self) -> Option<Range<usize>> {
+        let is_marker = |line: &FilterLine, marker: &str| line.text.trim() == marker;
+        let begin = self
+            .lines
+            .iter()
+            .position(|line| is_marker(line, BLOCK_BEGIN))?;
+        let end = self.lines[begin..]
+            .iter()
+            .position(|line| is_marker(line, BLOCK_END))?;
+        Some(begin..begin + end + 1)
+    }
+
+    /// Puts `block` in place of the lines the helper wrote before, or in front of every
+    /// other block if there are none. The lines of `block` get the line ending of the file.
+    pub fn set_helper_block(&mut self, block: &str) {
+        let ending = self.line_ending();
+        let mut lines = FilterFile::parse(block).lines;
+        for line in lines.iter_mut() {
+            line.ending = ending;
+        }
+        match self.helper_block() {
+            Some(range) => {
+                self.lines.splice(range, lines);
+            }
+            None => {
+                lines.push(FilterLine::parse("", ending));
+                self.lines.splice(0..0, lines);
+            }
+        }
+    }
+}
+
+impl fmt::Display for FilterFile {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.bom {
+            write!(f, "{}", BOM)?;
+        }
+        for line in self.lines.iter() {
+            f.write_str(&line.text)?;
+            f.write_str(line.ending.as_str())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{ChaosRecipeSet, ItemType, RecipeMode, RuleGenerator};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use strum_macros::AsRefStr;
+
+/// Influence of an item, as listed in its `influences` field.
+///
+/// Influenced rare sets are sold for exalted shards.
+#[derive(
+    Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize, AsRefStr,
+)]
+pub enum Influence {
+    Shaper,
+    Elder,
+    Crusader,
+    Redeemer,
+    Hunter,
+    Warlord,
+}
+
+impl Influence {
+    pub const ALL: [Influence; 6] = [
+        Influence::Shaper,
+        Influence::Elder,
+        Influence::Crusader,
+        Influence::Redeemer,
+        Influence::Hunter,
+        Influence::Warlord,
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|influence| influence.as_ref().eq_ignore_ascii_case(name))
+            .copied()
+    }
+}
+
+/// Reads `{"shaper": true, "elder": true}`, skipping unknown influences.
+pub(crate) fn from_map<'de, D>(d: D) -> std::result::Result<Vec<Influence>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    let map = HashMap::<String, bool>::deserialize(d)?;
+    let mut influences: Vec<_> = map
+        .into_iter()
+        .filter(|(_, has)| *has)
+        .filter_map(|(name, _)| Influence::from_name(&name))
+        .collect();
+    influences.sort_unstable();
+    Ok(influences)
+}
+
+/// Writes influences the way `from_map` reads them.
+pub(crate) fn to_map<S>(influences: &[Influence], s: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let map: HashMap<_, _> = influences
+        .iter()
+        .map(|influence| (influence.as_ref().to_ascii_lowercase(), true))
+        .collect();
+    map.serialize(s)
+}
+
+/// Influenced items of one influence in the stash.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct InfluenceSummary {
+    pub influence: Influence,
+    /// number of items in each slot
+    pub items: HashMap<ItemType, usize>,
+    /// complete sets of the influenced recipe they make
+    pub sets: usize,
+}
+
+/// Counts the influenced items of `map` per influence and slot. Influences without any item
+/// are left out. An item with two influences is counted for both.
+pub fn summarize_influences(map: &ChaosRecipeSet) -> Vec<InfluenceSummary> {
+    Influence::ALL
+        .iter()
+        .filter_map(|influence| {
+            let items: Vec<_> = map
+                .values()
+                .flat_map(|(chaos_list, regal_list)| chaos_list.iter().chain(regal_list.iter()))
+                .filter(|item| item.has_influence(*influence))
+                .cloned()
+                .collect();
+            if items.is_empty() {
+                return None;
+            }
+            let mut counts = HashMap::new();
+            for item in items.iter() {
+                *counts.entry(item.item_type()).or_default() += 1;
+            }
+            Some(InfluenceSummary {
+                influence: *influence,
+                items: counts,
+                sets: RuleGenerator::new(RecipeMode::Influenced.rule(), items).count(),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,221 @@
+use crate::{ChaosRecipeSet, Item, ItemType, RecipeMode};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// The player inventory a set is carried to the vendor in, in cells.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct InventoryGrid {
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Default for InventoryGrid {
+    fn default() -> Self {
+        Self {
+            width: 12,
+            height: 5,
+        }
+    }
+}
+
+/// Top-left cell of every item of a set, in the order of the set.
+pub type InventoryLayout = Vec<(usize, usize)>;
+
+impl InventoryGrid {
+    /// Finds a place for every item, or `None` if they can't be carried together.
+    pub fn pack(&self, items: &[Item]) -> Option<InventoryLayout> {
+        let total = area(items);
+        if total > self.width * self.height {
+            return None;
+        }
+        let mut cells = vec![false; self.width * self.height];
+        let mut positions = vec![None; items.len()];
+        if self.place(items, &mut cells, &mut positions, 0, total) {
+            positions.into_iter().collect()
+        } else {
+            None
+        }
+    }
+
+    /// Fills the cells from `cell` on, column by column like the game does. The first free
+    /// cell either gets the top-left corner of an item or stays empty.
+    fn place(
+        &self,
+        items: &[Item],
+        cells: &mut [bool],
+        positions: &mut [Option<(usize, usize)>],
+        cell: usize,
+        area: usize,
+    ) -> bool {
+        if area == 0 {
+            return true;
+        }
+        let cell = match (cell..cells.len()).find(|cell| !cells[*cell]) {
+            Some(cell) => cell,
+            None => return false,
+        };
+        if cells[cell..].iter().filter(|used| !**used).count() < area {
+            return false;
+        }
+        let (x, y) = (cell / self.height, cell % self.height);
+
+        let mut tried = Vec::new();
+        for idx in 0..items.len() {
+            let (w, h) = (items[idx].w, items[idx].h);
+            // 같은 크기의 아이템은 한 번만 놓아 보면 된다.
+            if positions[idx].is_some() || tried.contains(&(w, h)) {
+                continue;
+            }
+            tried.push((w, h));
+            if !self.is_free(cells, x, y, w, h) {
+                continue;
+            }
+            self.mark(cells, x, y, w, h, true);
+            positions[idx] = Some((x, y));
+            if self.place(items, cells, positions, cell + 1, area - w * h) {
+                return true;
+            }
+            positions[idx] = None;
+            self.mark(cells, x, y, w, h, false);
+        }
+
+        cells[cell] = true;
+        let found = self.place(items, cells, positions, cell + 1, area);
+        cells[cell] = false;
+        found
+    }
+
+    fn is_free(&self, cells: &[bool], x: usize, y: usize, w: usize, h: usize) -> bool {
+        x + w <= self.width
+            && y + h <= self.height
+            && (x..x + w).all(|x| (y..y + h).all(|y| !cells[x * self.height + y]))
+    }
+
+    fn mark(&self, cells: &mut [bool], x: usize, y: usize, w: usize, h: usize, used: bool) {
+        for x in x..x + w {
+            for y in y..y + h {
+                cells[x * self.height + y] = used;
+            }
+        }
+    }
+}
+
+fn area(items: &[Item]) -> usize {
+    items.iter().map(|item| item.w * item.h).sum()
+}
+
+/// Makes the sets of `queue` fit in `grid` where the items not in any set allow it.
+///
+/// Items of a set which doesn't fit are swapped for smaller unused items of the same type,
+/// and a two-handed weapon for two one-handed weapons or shields, the largest item first.
+/// The swaps keep the set valid for `mode` and don't change whether its items are
+/// identified. Returns the layout of every set, `None` for a set which still doesn't fit.
+pub fn fit_sets(
+    queue: &mut VecDeque<Vec<Item>>,
+    map: &ChaosRecipeSet,
+    mode: RecipeMode,
+    grid: &InventoryGrid,
+) -> VecDeque<Option<InventoryLayout>> {
+    let mut unused: Vec<Item> = map
+        .values()
+        .flat_map(|(chaos_list, regal_list)| chaos_list.iter().chain(regal_list.iter()))
+        .cloned()
+        .collect();
+    for item in queue.iter().flatten() {
+        if let Some(idx) = unused.iter().position(|unused| unused == item) {
+            unused.swap_remove(idx);
+        }
+    }
+    // 작은 아이템부터 바꿔 넣는다.
+    unused.sort_by_key(|item| item.w * item.h);
+
+    queue
+        .iter_mut()
+        .map(|set| {
+            if let Some(layout) = grid.pack(set) {
+                return Some(layout);
+            }
+            let mut candidate = set.clone();
+            let mut candidate_unused = unused.clone();
+            loop {
+                if let Some(layout) = grid.pack(&candidate) {
+                    *set = candidate;
+                    unused = candidate_unused;
+                    return Some(layout);
+                }
+                if !shrink(&mut candidate, &mut candidate_unused, mode) {
+                    return None;
+                }
+            }
+        })
+        .collect()
+}
+
+/// Swaps the largest item of `set` which has a smaller replacement in `unused`.
+fn shrink(set: &mut Vec<Item>, unused: &mut Vec<Item>, mode: RecipeMode) -> bool {
+    let mut order: Vec<usize> = (0..set.len()).collect();
+    order.sort_by_key(|idx| std::cmp::Reverse(set[*idx].w * set[*idx].h));
+
+    for idx in order {
+        let item = &set[idx];
+        let replacements: Vec<Vec<usize>> = match item.itype {
+            ItemType::Weapon2H => {
+                let one_handed: Vec<usize> = (0..unused.len())
+                    .filter(|u| {
+                        unused[*u].itype == ItemType::Weapon1HOrShield
+                            && unused[*u].identified == item.identified
+                    })
+                    .collect();
+                let mut options: Vec<Vec<usize>> = (0..unused.len())
+                    .filter(|u| {
+                        unused[*u].itype == ItemType::Weapon2H
+                            && unused[*u].identified == item.identified
+                    })
+                    .map(|u| vec![u])
+                    .collect();
+                for (n, first) in one_handed.iter().enumerate() {
+                    for second in one_handed[n + 1..].iter() {
+                        options.push(vec![*first, *second]);
+                    }
+                }
+                options
+            }
+            _ => (0..unused.len())
+                .filter(|u| {
+                    unused[*u].itype == item.itype && unused[*u].identified == item.identified
+                })
+                .map(|u| vec![u])
+                .collect(),
+        };
+
+        let item_area = item.w * item.h;
+        let best = replacements
+            .into_iter()
+            .filter(|replacement| {
+                let new_items: Vec<Item> = replacement.iter().map(|u| unused[*u].clone()).collect();
+                let mut new_set = set.clone();
+                new_set.remove(idx);
+                new_set.extend(new_items.iter().cloned());
+                area(&new_items) < item_area && mode.rule().follows(&new_set)
+            })
+            .min_by_key(|replacement| {
+                replacement
+                    .iter()
+                    .map(|u| unused[*u].w * unused[*u].h)
+                    .sum::<usize>()
+            });
+
+        if let Some(mut replacement) = best {
+            let old = set.remove(idx);
+            // 뒤에서부터 빼야 앞의 인덱스가 바뀌지 않는다.
+            replacement.sort_unstable_by(|a, b| b.cmp(a));
+            for u in replacement {
+                set.push(unused.remove(u));
+            }
+            unused.push(old);
+            unused.sort_by_key(|item| item.w * item.h);
+            return true;
+        }
+    }
+    false
+}
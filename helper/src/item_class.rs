@@ -0,0 +1,120 @@
+use crate::ItemType;
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+lazy_static! {
+    static ref BASE_TYPES: HashMap<String, BaseClass> =
+        serde_json::from_str(include_str!("../data/base_types.json")).unwrap();
+}
+
+/// Base types of one item class, read from `data/base_types.json`. The table is written by
+/// `examples/base_types.rs` from the base item list of RePoE.
+#[derive(Deserialize, Debug)]
+struct BaseClass {
+    slot: ItemType,
+    /// full base types like `Iron Ring`
+    bases: Vec<String>,
+}
+
+/// `extended` of an item in newer responses.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub(crate) struct Extended {
+    #[serde(default)]
+    category: Option<String>,
+    #[serde(default)]
+    subcategories: Vec<String>,
+}
+
+/// `category` of an item in older responses, like `{"weapons": ["claw"]}`.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub(crate) enum Category {
+    Map(HashMap<String, Vec<String>>),
+    Name(String),
+}
+
+/// Works out the slot of an item from its category, or from its base type if the category
+/// doesn't tell. `ItemType::Unclassified` if neither does.
+pub(crate) fn classify(
+    extended: Option<&Extended>,
+    category: Option<&Category>,
+    base_type: &str,
+) -> ItemType {
+    let extended = extended.and_then(|extended| {
+        let category = extended.category.as_deref()?;
+        from_category(category, &extended.subcategories)
+    });
+    let category = || match category? {
+        Category::Map(map) => map
+            .iter()
+            .find_map(|(category, subcategories)| from_category(category, subcategories)),
+        Category::Name(category) => from_category(category, &[]),
+    };
+    extended
+        .or_else(category)
+        .or_else(|| from_base_type(base_type))
+        .unwrap_or(ItemType::Unclassified)
+}
+
+/// `None` if an equipment category has no subcategory this knows.
+fn from_category(category: &str, subcategories: &[String]) -> Option<ItemType> {
+    match category {
+        "accessories" | "armour" | "weapons" => {}
+        // 보석, 플라스크, 지도 같은 것들은 레시피에 쓰이지 않는다.
+        _ => return Some(ItemType::Useless),
+    }
+    subcategories.iter().find_map(|sub| {
+        Some(match sub.as_str() {
+            "amulet" => ItemType::Amulet,
+            "ring" => ItemType::Ring,
+            "belt" => ItemType::Belt,
+            "trinket" => ItemType::Useless,
+            "gloves" => ItemType::Gloves,
+            "boots" => ItemType::Boots,
+            "helmet" => ItemType::Helmet,
+            "chest" => ItemType::Body,
+            "shield" => ItemType::Weapon1HOrShield,
+            "quiver" => ItemType::Useless,
+            "oneaxe" | "onemace" | "onesword" | "claw" | "dagger" | "runedagger" | "wand"
+            | "sceptre" => ItemType::Weapon1HOrShield,
+            "twoaxe" | "twomace" | "twosword" | "bow" | "staff" | "warstaff" => ItemType::Weapon2H,
+            "rod" => ItemType::Useless,
+            _ => return None,
+        })
+    })
+}
+
+/// Looks `base_type` up in the bundled table. A base type with words in front, like
+/// `Synthesised Iron Ring`, takes the slot of the longest base it ends with.
+fn from_base_type(base_type: &str) -> Option<ItemType> {
+    let mut best: Option<(usize, ItemType)> = None;
+    for class in BASE_TYPES.values() {
+        for base in class.bases.iter() {
+            let len = if base == base_type {
+                usize::MAX
+            } else if base_type.ends_with(base.as_str())
+                && base_type[..base_type.len() - base.len()].ends_with(' ')
+            {
+                base.len()
+            } else {
+                continue;
+            };
+            if !matches!(best, Some((best_len, _)) if best_len >= len) {
+                best = Some((len, class.slot));
+            }
+        }
+    }
+    best.map(|(_, slot)| slot)
+}
+
+/// Item classes of the bundled table whose items go in `slot`, sorted by name.
+pub(crate) fn classes_of(slot: ItemType) -> Vec<&'static str> {
+    let mut classes: Vec<_> = BASE_TYPES
+        .iter()
+        .filter(|(_, class)| class.slot == slot)
+        .map(|(name, _)| name.as_str())
+        .collect();
+    classes.sort_unstable();
+    classes
+}
@@ -0,0 +1,262 @@
+use crate::{
+    is_unidentified_set, HelperSession, Influence, Item, ItemType, RecipeMode, Result, StashEvent,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::thread::spawn;
+use std::time::{SystemTime, UNIX_EPOCH};
+use strum_macros::AsRefStr;
+
+/// What the vendor pays for a set.
+#[derive(
+    Clone, Copy, Hash, Eq, PartialEq, Ord, PartialOrd, Debug, Serialize, Deserialize, AsRefStr,
+)]
+pub enum Currency {
+    Chaos,
+    Regal,
+    ExaltedShard,
+}
+
+/// (currency, amount) a set of `mode` is sold for. Unidentified sets are paid twice.
+pub fn reward(mode: RecipeMode, unidentified: bool) -> (Currency, u32) {
+    let currency = match mode {
+        RecipeMode::Chaos => Currency::Chaos,
+        RecipeMode::Regal => Currency::Regal,
+        RecipeMode::Influenced => Currency::ExaltedShard,
+    };
+    (currency, if unidentified { 2 } else { 1 })
+}
+
+/// An item of a sold set, without where it was in the stash.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SoldItem {
+    pub base_type: String,
+    pub item_type: ItemType,
+    pub ilvl: usize,
+    pub identified: bool,
+    #[serde(default)]
+    pub influences: Vec<Influence>,
+}
+
+impl From<&Item> for SoldItem {
+    fn from(item: &Item) -> Self {
+        Self {
+            base_type: item.base_type.clone(),
+            item_type: item.item_type(),
+            ilvl: item.ilvl(),
+            identified: item.identified,
+            influences: item.influences(),
+        }
+    }
+}
+
+/// One line of the ledger.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sale {
+    /// unix time in seconds
+    pub time: u64,
+    /// unix time the recording session started at
+    pub session: u64,
+    pub league: String,
+    pub recipe: RecipeMode,
+    /// whether any item of the set was identified
+    pub identified: bool,
+    pub currency: Currency,
+    pub amount: u32,
+    pub items: Vec<SoldItem>,
+}
+
+impl Sale {
+    pub fn new(time: u64, session: u64, league: &str, recipe: RecipeMode, items: &[Item]) -> Self {
+        let unidentified = is_unidentified_set(items);
+        let (currency, amount) = reward(recipe, unidentified);
+        Self {
+            time,
+            session,
+            league: league.to_owned(),
+            recipe,
+            identified: !unidentified,
+            currency,
+            amount,
+            items: items.iter().map(SoldItem::from).collect(),
+        }
+    }
+}
+
+/// Sums of some sales.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Earnings {
+    pub sets: usize,
+    pub unidentified_sets: usize,
+    pub orbs: BTreeMap<Currency, u32>,
+    /// from the start of each recording session to its last sale
+    pub hours: f64,
+}
+
+impl Earnings {
+    pub fn of<'a>(sales: impl IntoIterator<Item = &'a Sale>) -> Self {
+        let mut earnings = Earnings::default();
+        let mut last_sales: HashMap<u64, u64> = HashMap::new();
+        for sale in sales {
+            earnings.sets += 1;
+            if !sale.identified {
+                earnings.unidentified_sets += 1;
+            }
+            *earnings.orbs.entry(sale.currency).or_default() += sale.amount;
+            let last = last_sales.entry(sale.session).or_default();
+            *last = (*last).max(sale.time);
+        }
+        earnings.hours = last_sales
+            .iter()
+            .map(|(session, last)| last.saturating_sub(*session) as f64 / 3600.0)
+            .sum();
+        earnings
+    }
+
+    /// Orbs of `currency` earned per hour, 0 if no time has passed.
+    pub fn per_hour(&self, currency: Currency) -> f64 {
+        match self.hours > 0.0 {
+            true => *self.orbs.get(&currency).unwrap_or(&0) as f64 / self.hours,
+            false => 0.0,
+        }
+    }
+}
+
+/// Earnings of each league.
+pub fn earnings_by_league(sales: &[Sale]) -> BTreeMap<String, Earnings> {
+    let mut leagues: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for sale in sales {
+        leagues.entry(sale.league.clone()).or_default().push(sale);
+    }
+    leagues
+        .into_iter()
+        .map(|(league, sales)| (league, Earnings::of(sales)))
+        .collect()
+}
+
+/// Earnings of each recording session, by the time it started.
+pub fn earnings_by_session(sales: &[Sale]) -> BTreeMap<u64, Earnings> {
+    let mut sessions: BTreeMap<_, Vec<_>> = BTreeMap::new();
+    for sale in sales {
+        sessions.entry(sale.session).or_default().push(sale);
+    }
+    sessions
+        .into_iter()
+        .map(|(session, sales)| (session, Earnings::of(sales)))
+        .collect()
+}
+
+/// History of the sold sets, kept as a file with one JSON `Sale` on each line.
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    path: PathBuf,
+}
+
+impl Ledger {
+    /// The file is created by the first sale.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append(&self, sale: &Sale) -> Result<()> {
+        let mut line = serde_json::to_vec(sale)?;
+        line.push(b'\n');
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+        // 쓰다 만 줄이 있으면 새 줄에 이어 쓰지 않는다.
+        if file.seek(SeekFrom::End(0))? > 0 {
+            let mut last = [0];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.insert(0, b'\n');
+            }
+        }
+        // 한 번에 써야 다른 프로그램이 반쯤 쓰인 줄을 읽지 않는다.
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Every sale so far, oldest first. There are none if the file doesn't exist yet.
+    pub fn sales(&self) -> Result<Vec<Sale>> {
+        Ok(self.read()?.0)
+    }
+
+    /// `sales`, and the number of lines which aren't sales, like one cut short by a crash.
+    /// They are skipped.
+    pub fn read(&self) -> Result<(Vec<Sale>, usize)> {
+        let file = match std::fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+            Err(e) => return Err(e.into()),
+        };
+        let mut sales = Vec::new();
+        let mut skipped = 0;
+        for line in BufReader::new(file).split(b'\n') {
+            let line = line?;
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            match serde_json::from_slice(&line) {
+                Ok(sale) => sales.push(sale),
+                Err(_) => skipped += 1,
+            }
+        }
+        Ok((sales, skipped))
+    }
+
+    /// Appends every set `session` sees consumed, until the session is dropped. Sales which
+    /// couldn't be written, e.g. while another program locks the file, are written with the
+    /// next one. Sets of another league than the one of the session are not sales.
+    pub fn record(&self, session: &HelperSession) -> Result<()> {
+        let events = session.subscribe()?;
+        let account = session.shared_account();
+        let ledger = self.clone();
+        let started = unix_time();
+        spawn(move || {
+            let mut unwritten: Vec<Sale> = Vec::new();
+            for event in events {
+                if let StashEvent::SetConsumed {
+                    mode,
+                    items,
+                    league,
+                    ..
+                } = event
+                {
+                    // 리그를 바꾸는 사이에 사라진 세트는 판 것이 아니다.
+                    if league != account.read().unwrap().league {
+                        continue;
+                    }
+                    unwritten.push(Sale::new(unix_time(), started, &league, mode, &items));
+                    // 순서가 바뀌지 않도록 앞의 판매부터 쓴다.
+                    while let Some(sale) = unwritten.first() {
+                        match ledger.append(sale) {
+                            Ok(()) => {
+                                unwritten.remove(0);
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
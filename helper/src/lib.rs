@@ -1,29 +1,58 @@
-use anyhow::{anyhow, Result};
 use either::Either;
 use lazy_static::lazy_static;
-use regex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, VecDeque};
-use std::fmt::{self, Formatter};
+use std::fmt;
 use std::iter::Iterator;
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    mpsc, Mutex, RwLock,
+use std::time::Duration;
+use strum_macros::*;
+
+mod client_log;
+mod error;
+mod event;
+mod filter;
+mod filter_file;
+mod influence;
+mod inventory;
+mod item_class;
+mod ledger;
+mod optimize;
+mod rate_limit;
+mod realm;
+mod recipe;
+mod server;
+mod session;
+mod simulate;
+mod source;
+mod tab;
+pub use client_log::{AreaKind, ClientLog, LogEvent, LogWatcher, DEFAULT_LOG_POLL_INTERVAL};
+pub use error::{HelperError, Result};
+pub use event::StashEvent;
+pub use filter::{inject_block, slot_counts, LootFilter, SlotCount, BLOCK_BEGIN, BLOCK_END};
+pub use filter_file::{
+    FilterBlock, FilterFile, FilterLine, FilterRule, LineEnding, LineKind, Visibility,
+};
+pub use influence::{summarize_influences, Influence, InfluenceSummary};
+pub use inventory::{fit_sets, InventoryGrid, InventoryLayout};
+pub use ledger::{
+    earnings_by_league, earnings_by_session, reward, Currency, Earnings, Ledger, Sale, SoldItem,
+};
+pub use optimize::{optimal_chaos_sets, optimal_sets};
+pub use rate_limit::{RateLimitRule, RateLimiter};
+pub use realm::Realm;
+pub use recipe::{
+    Identification, IlvlBand, InfluenceRule, Rarity, RecipeRule, Requirement, RuleGenerator,
+    SlotItems, SlotRule,
 };
-use std::thread::spawn;
+pub use server::ApiServer;
+pub use session::{HelperSession, DEFAULT_REFRESH_TIMEOUT};
+pub use simulate::{simulate, Generator, ItemSpec, SimulationReport, StashSpec, Violation};
+pub use source::{FileSource, LiveSource, MemorySource, StashSource};
+pub use tab::{StashTab, TabColour, TabSelector};
 
 lazy_static! {
     static ref CLIENT: Client = Client::new();
-    static ref ACCOUNT: RwLock<AccountData> = RwLock::new(AccountData {
-        account: String::new(),
-        cookie: String::new(),
-        league: String::new(),
-        tab_idx: 0,
-    });
-    static ref NET_THREAD_SENDER: Mutex<Option<mpsc::Sender<InternalMessage>>> = Mutex::new(None);
-    static ref DEBUG_QUEUE: Mutex<Vec<String>> = Mutex::new(Vec::new());
-    static ref IS_QUAD_STASH: AtomicBool = AtomicBool::new(false);
 }
 
 #[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -31,7 +60,32 @@ pub struct AccountData {
     pub account: String,
     pub cookie: String,
     pub league: String,
-    pub tab_idx: usize,
+    /// stash tabs to scan, saved as `tab_idx` or `tab_indices` by older versions
+    #[serde(
+        default,
+        alias = "tab_idx",
+        alias = "tab_indices",
+        deserialize_with = "one_or_many"
+    )]
+    pub stash_tabs: Vec<TabSelector>,
+    #[serde(default)]
+    pub realm: Realm,
+}
+
+fn one_or_many<'de, D>(d: D) -> std::result::Result<Vec<TabSelector>, D::Error>
+where
+    D: serde::de::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(TabSelector),
+        Many(Vec<TabSelector>),
+    }
+    Ok(match OneOrMany::deserialize(d)? {
+        OneOrMany::One(tab) => vec![tab],
+        OneOrMany::Many(tabs) => tabs,
+    })
 }
 
 #[derive(Deserialize)]
@@ -42,45 +96,175 @@ struct League {
 #[derive(Deserialize)]
 struct LeagueList(Vec<League>);
 
-pub fn get_league_list() -> Result<Vec<String>> {
-    CLIENT
-        .get("http://api.pathofexile.com/leagues")
-        .query(&[("compact", 1)])
-        .send()
-        .and_then(|mut res| {
-            let val: LeagueList = res.json()?;
-            Ok(val.0.into_iter().map(|league| league.id).collect())
-        })
-        .map_err(|e| anyhow!(e))
+pub fn get_league_list(realm: &Realm) -> Result<Vec<String>> {
+    let mut res = CLIENT
+        .get(&realm.league_url())
+        .query(&[("compact", "1"), ("realm", realm.realm_code())])
+        .send()?
+        .error_for_status()?;
+    let val: LeagueList = serde_json::from_str(&res.text()?)?;
+    Ok(val.0.into_iter().map(|league| league.id).collect())
+}
+
+/// (Chaos-able-items, Regal-able-items)
+pub type ClassifiedRecipeLists = (Vec<Item>, Vec<Item>);
+/// <ItemType, (Chaos-able-items, Regal-able-items)>
+pub type ChaosRecipeSet = HashMap<ItemType, ClassifiedRecipeLists>;
+
+/// Groups the items of a stash which fit the chaos or the regal recipe by item type, and
+/// splits them by whether they meet the requirements of the chaos recipe.
+pub fn classify_items(items: Vec<Item>) -> ChaosRecipeSet {
+    let chaos_rule = RecipeMode::Chaos.rule();
+    let regal_rule = RecipeMode::Regal.rule();
+    let mut map: ChaosRecipeSet = HashMap::new();
+    for item in items {
+        if !chaos_rule.accepts(&item) && !regal_rule.accepts(&item) {
+            continue;
+        }
+        let (chaos_list, regal_list) = map.entry(item.itype).or_default();
+        if chaos_rule.is_required(&item) {
+            chaos_list.push(item);
+        } else {
+            regal_list.push(item);
+        }
+    }
+    sort_by_size(&mut map);
+    map
 }
 
-pub fn init_module() {
-    let (sender, receiver) = mpsc::channel();
-    spawn(network_thread_func(receiver));
-    let mut g_sender = NET_THREAD_SENDER.lock().unwrap();
-    *g_sender = Some(sender);
+/// Items whose slot couldn't be worked out from their category or base type.
+pub fn unclassified_items(items: &[Item]) -> Vec<Item> {
+    items
+        .iter()
+        .filter(|item| item.itype == ItemType::Unclassified)
+        .cloned()
+        .collect()
 }
 
-pub fn set_account(new_account: AccountData) {
-    let mut g_account = ACCOUNT.write().unwrap();
-    if *g_account == new_account {
-        return;
+fn sort_by_size(map: &mut ChaosRecipeSet) {
+    for (chaos_list, regal_list) in map.values_mut() {
+        chaos_list.sort_unstable_by(|a, b| (a.w * a.h).cmp(&(b.w * b.h)).reverse());
+        regal_list.sort_unstable_by(|a, b| (a.w * a.h).cmp(&(b.w * b.h)).reverse());
     }
-    *g_account = new_account;
 }
 
-/// (Chaos-able-items, Regal-able-items)
-type ClassifiedRecipeLists = (Vec<Item>, Vec<Item>);
-/// <ItemType, (Chaos-able-items, Regal-able-items)>
-type ChaosRecipeSet = HashMap<ItemType, ClassifiedRecipeLists>;
+/// Stash tabs the player has to visit to pick up `items`.
+pub fn tabs_of_set(items: &[Item]) -> Vec<usize> {
+    let mut tabs: Vec<_> = items.iter().map(|item| item.tab_idx).collect();
+    tabs.sort_unstable();
+    tabs.dedup();
+    tabs
+}
+
+/// Items of `set` which are not in `snapshot` anymore, or not where they were.
+pub fn removed_items(set: &[Item], snapshot: &[Item]) -> Vec<Item> {
+    set.iter()
+        .filter(|item| !snapshot.iter().any(|other| item.same_place(other)))
+        .cloned()
+        .collect()
+}
+
+/// An unidentified set is paid twice as much as an identified one.
+pub fn is_unidentified_set(items: &[Item]) -> bool {
+    !items.is_empty() && items.iter().all(|item| !item.identified)
+}
+
+/// Splits `map` into (unidentified items, identified items).
+fn split_by_identification(map: &ChaosRecipeSet) -> (ChaosRecipeSet, ChaosRecipeSet) {
+    let mut unidentified = ChaosRecipeSet::new();
+    let mut identified = ChaosRecipeSet::new();
+    for (i_type, (chaos_list, regal_list)) in map {
+        let (c_unid, c_id) = chaos_list
+            .iter()
+            .cloned()
+            .partition(|item| !item.identified);
+        let (r_unid, r_id) = regal_list
+            .iter()
+            .cloned()
+            .partition(|item| !item.identified);
+        unidentified.insert(*i_type, (c_unid, r_unid));
+        identified.insert(*i_type, (c_id, r_id));
+    }
+    (unidentified, identified)
+}
+
+const RECIPE_WITHOUT_WEAPONS: [ItemType; 8] = [
+    ItemType::Amulet,
+    ItemType::Belt,
+    ItemType::Body,
+    ItemType::Boots,
+    ItemType::Gloves,
+    ItemType::Helmet,
+    ItemType::Ring,
+    ItemType::Ring,
+];
+
+/// Which vendor recipe the helper builds sets for.
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Debug, Default, Serialize, Deserialize, AsRefStr)]
+pub enum RecipeMode {
+    /// At least one item is below ilvl 75.
+    #[default]
+    Chaos,
+    /// Every item is ilvl 75 or above.
+    Regal,
+    /// Every item shares an influence.
+    Influenced,
+}
+
+impl RecipeMode {
+    pub const ALL: [RecipeMode; 3] = [RecipeMode::Chaos, RecipeMode::Regal, RecipeMode::Influenced];
+}
+
+impl fmt::Display for RecipeMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+/// Builds every set the stash supports for `mode`, in the order they will be shown.
+///
+/// Fully unidentified sets are built first, only from unidentified items. The unidentified
+/// items left over from them are then mixed with the identified ones.
+pub fn recipe_queue(map: &ChaosRecipeSet, mode: RecipeMode) -> VecDeque<Vec<Item>> {
+    let (unidentified, mut rest) = split_by_identification(map);
+    let (mut queue, leftover) = build_sets(&unidentified, mode);
+    for (i_type, (mut chaos_list, mut regal_list)) in leftover {
+        let lists = rest.entry(i_type).or_default();
+        lists.0.append(&mut chaos_list);
+        lists.1.append(&mut regal_list);
+    }
+    sort_by_size(&mut rest);
+    queue.extend(build_sets(&rest, mode).0);
+    queue
+}
+
+/// Returns the sets built from `map` and the items which are not used by them.
+///
+/// Recipes with slots like the chaos recipe get the most sets `optimal_sets` finds, any
+/// other recipe the sets of `RuleGenerator`.
+fn build_sets(map: &ChaosRecipeSet, mode: RecipeMode) -> (VecDeque<Vec<Item>>, ChaosRecipeSet) {
+    if let Some(built) = optimal_sets(map, mode.rule()) {
+        return built;
+    }
+    let items = map
+        .values()
+        .flat_map(|(chaos_list, regal_list)| chaos_list.iter().chain(regal_list.iter()))
+        .cloned();
+    let mut generator = RuleGenerator::new(mode.rule(), items);
+    let sets = generator.by_ref().collect();
+    (sets, classify_items(generator.remaining()))
+}
 
+/// Picks the items of each chaos set greedily, slot by slot.
+///
+/// `optimal_chaos_sets` builds at least as many sets from the same items.
 #[derive(Clone)]
-struct ChaosListGenerator<'a> {
+pub struct ChaosListGenerator<'a> {
     stash_items: HashMap<ItemType, (&'a [Item], &'a [Item])>,
 }
 
 impl<'a> ChaosListGenerator<'a> {
-    fn new(map: &'a ChaosRecipeSet) -> Self {
+    pub fn new(map: &'a ChaosRecipeSet) -> Self {
         Self {
             stash_items: map
                 .iter()
@@ -89,6 +273,14 @@ impl<'a> ChaosListGenerator<'a> {
         }
     }
 
+    /// Items which have not been put in any set yet.
+    pub fn remaining(&self) -> ChaosRecipeSet {
+        self.stash_items
+            .iter()
+            .map(|(k, (c, r))| (*k, (c.to_vec(), r.to_vec())))
+            .collect()
+    }
+
     fn get_item_by_type(
         &mut self,
         i_type: ItemType,
@@ -146,7 +338,7 @@ impl<'a> ChaosListGenerator<'a> {
                     }
                 })
             })
-            .map(|item| Either::Right(item))
+            .map(Either::Right)
             .or_else(|| {
                 self.stash_items
                     .get_mut(&ItemType::Weapon1HOrShield)
@@ -173,7 +365,7 @@ impl<'a> ChaosListGenerator<'a> {
                             )
                         }),
                     })
-                    .map(|items| Either::Left(items))
+                    .map(Either::Left)
             })
     }
 }
@@ -182,21 +374,22 @@ impl<'a> Iterator for ChaosListGenerator<'a> {
     type Item = Vec<Item>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // 무기가 아닌 것들을 모아서 하나씩 벡터에 넣는다.
-        let recipe_without_weapons: [ItemType; 8] = [
-            ItemType::Amulet,
-            ItemType::Belt,
-            ItemType::Body,
-            ItemType::Boots,
-            ItemType::Gloves,
-            ItemType::Helmet,
-            ItemType::Ring,
-            ItemType::Ring,
-        ];
+        // 세트를 다 채우지 못하면 꺼냈던 아이템들을 되돌린다.
+        let backup = self.stash_items.clone();
+        let result = self.take_set();
+        if result.is_none() {
+            self.stash_items = backup;
+        }
+        result
+    }
+}
 
+impl<'a> ChaosListGenerator<'a> {
+    fn take_set(&mut self) -> Option<Vec<Item>> {
+        // 무기가 아닌 것들을 모아서 하나씩 벡터에 넣는다.
         let mut can_make_chaos = false;
-        let result_vec: Option<Self::Item> =
-            recipe_without_weapons
+        let result_vec: Option<Vec<Item>> =
+            RECIPE_WITHOUT_WEAPONS
                 .iter()
                 .cloned()
                 .try_fold(vec![], |mut vec, i_type| {
@@ -233,177 +426,179 @@ impl<'a> Iterator for ChaosListGenerator<'a> {
     }
 }
 
-fn network_thread_func(recv: mpsc::Receiver<InternalMessage>) -> impl FnOnce() -> () {
-    move || {
-        let (in_send, in_recv) = mpsc::sync_channel::<()>(1);
-        let (data_send, data_recv) = mpsc::channel::<Result<ChaosRecipeSet>>();
-        {
-            spawn(move || {
-                for _ in in_recv.iter() {
-                    match get_stash_data_in() {
-                        Ok(stash_data) => {
-                            {
-                                IS_QUAD_STASH.store(stash_data.quad_layout, Ordering::Relaxed);
-                            }
-                            let mut map: ChaosRecipeSet = HashMap::new();
-                            for item in stash_data.items {
-                                if item.ilvl < 60 || item.frame_type != 2 {
-                                    continue;
-                                }
-                                let (chaos_list, regal_list) = map.entry(item.itype).or_default();
-                                if item.ilvl < 75 {
-                                    chaos_list.push(item);
-                                } else {
-                                    regal_list.push(item);
-                                }
-                            }
-                            for (chaos_list, regal_list) in map.values_mut() {
-                                chaos_list.sort_unstable_by(|a, b| {
-                                    (a.w * a.h).cmp(&(b.w * b.h)).reverse()
-                                });
-                                regal_list.sort_unstable_by(|a, b| {
-                                    (a.w * a.h).cmp(&(b.w * b.h)).reverse()
-                                });
-                            }
-                            data_send.send(Ok(map)).unwrap();
-                        }
-                        Err(e) => data_send.send(Err(e)).unwrap(),
-                    }
-                }
-            });
-        }
-
-        let mut map: ChaosRecipeSet = HashMap::new();
-        let mut chaos_queue: VecDeque<Vec<Item>> = ChaosListGenerator::new(&map).collect();
-        let mut total_count = chaos_queue.len();
-
-        for msg in recv.iter() {
-            let is_quad_stash = IS_QUAD_STASH.load(Ordering::Relaxed);
-            match msg {
-                InternalMessage::RequestChaosRecipe(sender) => match chaos_queue.pop_front() {
-                    Some(chaos_list) => sender
-                        .send(Ok(ResponseFromNetwork::ChaosRecipe((
-                            chaos_list,
-                            is_quad_stash,
-                        ))))
-                        .unwrap(),
-                    None => sender
-                        .send(Ok(ResponseFromNetwork::ChaosRecipe((
-                            Vec::new(),
-                            is_quad_stash,
-                        ))))
-                        .unwrap(),
-                },
-                InternalMessage::RequestStashStatus(sender) => {
-                    in_send.try_send(()).ok();
-                    let recv_result = data_recv.try_iter().last();
-                    match recv_result {
-                        Some(Ok(new_map)) => {
-                            map = new_map;
-                            chaos_queue = ChaosListGenerator::new(&map).collect();
-                            total_count = chaos_queue.len();
-                            sender
-                                .send(Ok(ResponseFromNetwork::StashStatus((
-                                    map.clone(),
-                                    total_count,
-                                ))))
-                                .unwrap();
-                        }
-                        Some(Err(e)) => {
-                            sender.send(Err(e)).unwrap();
-                        }
-                        None => {
-                            chaos_queue = ChaosListGenerator::new(&map).collect();
-                            sender
-                                .send(Ok(ResponseFromNetwork::StashStatus((
-                                    map.clone(),
-                                    total_count,
-                                ))))
-                                .unwrap();
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
-
-pub fn acquire_chaos_list(requre_whole: bool) -> Result<ResponseFromNetwork> {
-    let (sender, receiver) = mpsc::channel();
-    let g_sender = NET_THREAD_SENDER.lock().unwrap();
-    g_sender
-        .as_ref()
-        .unwrap()
-        .send(match requre_whole {
-            true => InternalMessage::RequestStashStatus(sender),
-            false => InternalMessage::RequestChaosRecipe(sender),
-        })
-        .map_err(|e| anyhow!("{}", e))?;
-    let ret_val = match receiver.iter().last() {
-        Some(val @ Ok(_)) => val,
-        Some(val @ Err(_)) => val,
-        _ => Err(anyhow::anyhow!("Network Thread Channel has send nothing")),
-    };
-    ret_val
-}
-
-#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
+/// An item of the stash. It is written as JSON like the API sends it, plus what the helper
+/// worked out, so it can be read back.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(from = "ItemData", into = "ItemData")]
 pub struct Item {
+    /// only sent by newer responses
+    id: Option<String>,
     pub w: usize,
     pub h: usize,
     pub x: usize,
     pub y: usize,
     ilvl: usize,
-    #[serde(rename = "frameType")]
     frame_type: usize, // number 2 is unique
-    #[serde(deserialize_with = "item_type_from_icon", rename = "icon")]
+    pub identified: bool,
+    influences: Vec<Influence>,
+    pub base_type: String,
+    /// index of the stash tab the item is in
+    pub tab_idx: usize,
+    /// whether that stash tab is a quad tab
+    pub quad_tab: bool,
     itype: ItemType,
 }
 
-fn item_type_from_icon<'de, D>(d: D) -> Result<ItemType, D::Error>
-where
-    D: serde::de::Deserializer<'de>,
-{
-    let visitor = ItemTypeVisitor;
-    d.deserialize_identifier(visitor)
+impl Item {
+    pub fn ilvl(&self) -> usize {
+        self.ilvl
+    }
+
+    pub fn item_type(&self) -> ItemType {
+        self.itype
+    }
+
+    pub fn influences(&self) -> Vec<Influence> {
+        self.influences.clone()
+    }
+
+    pub fn has_influence(&self, influence: Influence) -> bool {
+        self.influences.contains(&influence)
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    /// Whether `other` is this item, lying where it was. Items without an id are told
+    /// apart by their base type.
+    pub fn same_place(&self, other: &Item) -> bool {
+        let same_item = match (&self.id, &other.id) {
+            (Some(id), Some(other_id)) => id == other_id,
+            _ => self.base_type == other.base_type,
+        };
+        same_item && (self.tab_idx, self.x, self.y) == (other.tab_idx, other.x, other.y)
+    }
 }
 
-#[derive(Deserialize, Debug)]
-struct StashData {
-    items: Vec<Item>,
-    #[serde(default, rename = "quadLayout")]
-    quad_layout: bool,
+/// An item as the API sends it, and as the helper writes it.
+#[derive(Serialize, Deserialize)]
+struct ItemData {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    w: usize,
+    h: usize,
+    x: usize,
+    y: usize,
+    ilvl: usize,
+    #[serde(rename = "frameType")]
+    frame_type: usize,
+    #[serde(default = "default_identified")]
+    identified: bool,
+    #[serde(
+        default,
+        deserialize_with = "influence::from_map",
+        serialize_with = "influence::to_map"
+    )]
+    influences: Vec<Influence>,
+    /// older responses have flags instead of `influences`
+    #[serde(default, skip_serializing)]
+    shaper: bool,
+    #[serde(default, skip_serializing)]
+    elder: bool,
+    /// only sent by newer responses, `typeLine` is the base type of rare items
+    #[serde(default, rename = "baseType")]
+    base_type: Option<String>,
+    #[serde(default, rename = "typeLine", skip_serializing)]
+    type_line: String,
+    #[serde(default, skip_serializing)]
+    extended: Option<item_class::Extended>,
+    #[serde(default, skip_serializing)]
+    category: Option<item_class::Category>,
+    /// the rest are never sent by the API, only written by the helper
+    #[serde(default)]
+    item_type: Option<ItemType>,
+    #[serde(default)]
+    tab_idx: usize,
+    #[serde(default)]
+    quad_tab: bool,
 }
 
-fn get_stash_data_in() -> Result<StashData> {
-    let res;
-    let account;
-    {
-        let g_account = ACCOUNT.read().unwrap();
-        account = g_account.clone();
+impl From<ItemData> for Item {
+    fn from(data: ItemData) -> Self {
+        let base_type = data.base_type.unwrap_or(data.type_line);
+        let itype = match data.item_type {
+            Some(itype) => itype,
+            None => {
+                item_class::classify(data.extended.as_ref(), data.category.as_ref(), &base_type)
+            }
+        };
+        let mut influences = data.influences;
+        if data.shaper && !influences.contains(&Influence::Shaper) {
+            influences.push(Influence::Shaper);
+        }
+        if data.elder && !influences.contains(&Influence::Elder) {
+            influences.push(Influence::Elder);
+        }
+        influences.sort_unstable();
+        Self {
+            id: data.id,
+            w: data.w,
+            h: data.h,
+            x: data.x,
+            y: data.y,
+            ilvl: data.ilvl,
+            frame_type: data.frame_type,
+            identified: data.identified,
+            influences,
+            base_type,
+            tab_idx: data.tab_idx,
+            quad_tab: data.quad_tab,
+            itype,
+        }
     }
-    res = CLIENT
-        .get("https://poe.game.daum.net/character-window/get-stash-items")
-        .query(&[
-            ("accountName", account.account.as_str()),
-            ("realm", "pc"),
-            ("league", account.league.as_str()),
-        ])
-        .query(&[("tabs", 0)])
-        .query(&[("tabIndex", account.tab_idx)])
-        .query(&[("public", false)])
-        .header("Cookie", account.cookie)
-        // .header("Host", "www.pathofexile.com")
-        // .header("Connection", "Keep-Alive")
-        .send()?;
-    match res.error_for_status() {
-        Ok(mut res) => res.json().map_err(|e| anyhow!(e)),
-        Err(e) => Err(anyhow!(e)),
+}
+
+impl From<Item> for ItemData {
+    fn from(item: Item) -> Self {
+        Self {
+            id: item.id,
+            w: item.w,
+            h: item.h,
+            x: item.x,
+            y: item.y,
+            ilvl: item.ilvl,
+            frame_type: item.frame_type,
+            identified: item.identified,
+            influences: item.influences,
+            shaper: false,
+            elder: false,
+            base_type: Some(item.base_type),
+            type_line: String::new(),
+            extended: None,
+            category: None,
+            item_type: Some(item.itype),
+            tab_idx: item.tab_idx,
+            quad_tab: item.quad_tab,
+        }
     }
 }
 
-use strum_macros::*;
-#[derive(Clone, Copy, Hash, Eq, PartialEq, Debug, AsRefStr)]
+fn default_identified() -> bool {
+    true
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StashData {
+    pub items: Vec<Item>,
+    #[serde(default, rename = "quadLayout")]
+    pub quad_layout: bool,
+    /// only sent when the tab list is requested
+    #[serde(default)]
+    pub tabs: Vec<StashTab>,
+}
+
+#[derive(Clone, Copy, Hash, Eq, PartialEq, Debug, Serialize, Deserialize, AsRefStr)]
 pub enum ItemType {
     Weapon1HOrShield,
     Weapon2H,
@@ -414,61 +609,46 @@ pub enum ItemType {
     Ring,
     Amulet,
     Belt,
+    /// not worn in any recipe slot, like quivers, jewels and flasks
     Useless,
+    /// neither the category nor the base type of the item is known
+    Unclassified,
 }
 
-struct ItemTypeVisitor;
-
-impl<'de> serde::de::Visitor<'de> for ItemTypeVisitor {
-    type Value = ItemType;
-
-    fn expecting(&self, fomatter: &mut Formatter) -> fmt::Result {
-        write!(fomatter, "a icon image url which contains item types")
-    }
-
-    fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
-    where
-        E: serde::de::Error,
-    {
-        use regex::Regex;
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"/2DItems/(.+?)/(.+?)(\.png|/)").unwrap();
-        }
-        let cap = RE.captures(s);
-        if let Some(cap) = cap {
-            match (
-                cap.get(1).map(|m| m.as_str()),
-                cap.get(2).map(|m| m.as_str()),
-            ) {
-                (Some("Armours"), Some("Boots")) => Ok(ItemType::Boots),
-                (Some("Armours"), Some("Helmets")) => Ok(ItemType::Helmet),
-                (Some("Armours"), Some("Gloves")) => Ok(ItemType::Gloves),
-                (Some("Armours"), Some("BodyArmours")) => Ok(ItemType::Body),
-                (Some("Armours"), Some("Shields")) => Ok(ItemType::Weapon1HOrShield),
-                (Some("Weapons"), Some("OneHandWeapons")) => Ok(ItemType::Weapon1HOrShield),
-                (Some("Weapons"), Some("TwoHandWeapons")) => Ok(ItemType::Weapon2H),
-                (Some("Weapons"), Some("Bows")) => Ok(ItemType::Weapon2H),
-                (Some("Amulets"), _) => Ok(ItemType::Amulet),
-                (Some("Rings"), _) => Ok(ItemType::Ring),
-                (Some("Belts"), _) => Ok(ItemType::Belt),
-                _ => Ok(ItemType::Useless),
-            }
-        } else {
-            Ok(ItemType::Useless)
-        }
-    }
-}
-
-#[derive(Clone)]
-enum InternalMessage {
-    RequestChaosRecipe(mpsc::Sender<Result<ResponseFromNetwork>>),
-    RequestStashStatus(mpsc::Sender<Result<ResponseFromNetwork>>),
-}
-
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "kind")]
 pub enum ResponseFromNetwork {
-    /// items in a chaos recipe and whether it's quad stash
-    ChaosRecipe((Vec<Item>, bool)),
-    /// recipe set and total able chaos orbs
-    StashStatus((ChaosRecipeSet, usize)),
+    ChaosRecipe {
+        /// items in a recipe
+        items: Vec<Item>,
+        /// stash tabs they are in
+        tabs: Vec<usize>,
+        /// names of `tabs`, or their indices for tabs chosen by index
+        tab_names: Vec<String>,
+        /// the recipe they were picked for
+        mode: RecipeMode,
+        /// where the items go in the inventory, `None` if they don't fit at once
+        layout: Option<InventoryLayout>,
+        /// items of the set which have already been taken out of the stash
+        picked: Vec<Item>,
+    },
+    StashStatus {
+        recipe_set: ChaosRecipeSet,
+        /// total able sets, counting the one being picked up
+        sets: usize,
+        /// fully unidentified sets among them
+        unidentified_sets: usize,
+        /// the recipe they were counted for
+        mode: RecipeMode,
+        /// influenced items and sets, for every influence in the stash
+        influenced: Vec<InfluenceSummary>,
+        /// items of the stash whose slot is unknown, so they are in no set
+        unclassified: Vec<Item>,
+        /// time left until the stash can be requested again
+        cooldown: Option<Duration>,
+        /// counts up with every fetch, 0 before the first one
+        generation: u64,
+        /// time since the data was fetched
+        age: Option<Duration>,
+    },
 }
@@ -0,0 +1,273 @@
+use crate::{ChaosRecipeSet, Item, ItemType, RecipeMode, RecipeRule, SlotItems, SlotRule};
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// Slots of a recipe as the optimiser fills them.
+struct Layout {
+    /// `(type, items per set)` of every slot, the options of the choice first
+    slots: Vec<(ItemType, usize)>,
+    /// whether the first two slots are a choice, like a two-handed weapon or two one-handed
+    /// ones
+    choice: bool,
+}
+
+impl Layout {
+    /// `None` unless every slot of `rule` is of one type, there is at most one choice and
+    /// it is between two such slots, and the only requirement is the one item of the chaos
+    /// recipe.
+    fn of(rule: &RecipeRule) -> Option<Self> {
+        // 카오스 목록에는 카오스 레시피가 요구하는 아이템이 들어 있다.
+        match rule.require.as_slice() {
+            [requirement]
+                if requirement.count == 1 && rule.require == RecipeMode::Chaos.rule().require => {}
+            _ => return None,
+        }
+        let single = |items: &SlotItems| match items.types.as_slice() {
+            [i_type] => Some((*i_type, items.count)),
+            _ => None,
+        };
+        let mut choice = Vec::new();
+        let mut slots = Vec::new();
+        for slot in rule.slots.iter() {
+            match slot {
+                SlotRule::Items(items) => slots.push(single(items)?),
+                SlotRule::OneOf { one_of } if choice.is_empty() => match one_of.as_slice() {
+                    [first, second] => match (first.as_slice(), second.as_slice()) {
+                        ([first], [second]) => choice = vec![single(first)?, single(second)?],
+                        _ => return None,
+                    },
+                    _ => return None,
+                },
+                SlotRule::OneOf { .. } => return None,
+            }
+        }
+        let layout = Layout {
+            choice: !choice.is_empty(),
+            slots: choice.into_iter().chain(slots).collect(),
+        };
+        // 같은 종류를 두 슬롯에서 쓰면 아이템이 겹친다.
+        let mut types: Vec<_> = layout.slots.iter().map(|(i_type, _)| *i_type).collect();
+        types.sort_by_key(|i_type| i_type.as_ref().to_owned());
+        types.dedup();
+        match types.len() == layout.slots.len() {
+            true => Some(layout),
+            false => None,
+        }
+    }
+}
+
+/// Items of one type which go into the sets in `sets`, `per_set` items each.
+struct Slot<'a> {
+    i_type: ItemType,
+    per_set: usize,
+    sets: Range<usize>,
+    chaos: &'a [Item],
+    regal: &'a [Item],
+    /// how many of `chaos` are put in sets, the rest of the slot is filled from `regal`
+    chaos_used: usize,
+}
+
+impl<'a> Slot<'a> {
+    fn needed(&self) -> usize {
+        self.per_set * self.sets.len()
+    }
+
+    /// Sets which get a chaos item from this slot.
+    fn covered(&self) -> usize {
+        self.chaos_used.min(self.sets.len())
+    }
+}
+
+fn lists(map: &ChaosRecipeSet, i_type: ItemType) -> (&[Item], &[Item]) {
+    map.get(&i_type)
+        .map(|(chaos, regal)| (chaos.as_slice(), regal.as_slice()))
+        .unwrap_or((&[], &[]))
+}
+
+/// Builds as many chaos recipe sets as `map` supports, with the slots of
+/// `recipes/chaos.json`. There are none if `optimal_sets` can't handle them.
+pub fn optimal_chaos_sets(map: &ChaosRecipeSet) -> (VecDeque<Vec<Item>>, ChaosRecipeSet) {
+    optimal_sets(map, RecipeMode::Chaos.rule()).unwrap_or_else(|| (VecDeque::new(), map.clone()))
+}
+
+/// Builds as many sets of `rule` as `map` supports, or `None` if the slots or the
+/// requirements of `rule` are not like the ones of the chaos recipe.
+///
+/// The number of sets is worked out from the item counts before any item is picked, so no
+/// set is lost to an item below ilvl 75 spent where an item of ilvl 75 or above would do.
+/// Among the ways to build the most sets, the one with the most items of the first option
+/// of the choice, like two-handed weapons, is used. Items are taken from the front of their
+/// lists, and chaos items only replace regal items where a set has no chaos item yet.
+///
+/// Returns the sets and the items which are not used by them.
+pub fn optimal_sets(
+    map: &ChaosRecipeSet,
+    rule: &RecipeRule,
+) -> Option<(VecDeque<Vec<Item>>, ChaosRecipeSet)> {
+    let layout = Layout::of(rule)?;
+    // 레시피가 받지 않는 아이템은 그대로 남긴다.
+    let mut accepted = map.clone();
+    let mut rejected = ChaosRecipeSet::new();
+    for (i_type, (chaos, regal)) in accepted.iter_mut() {
+        let (chaos_out, regal_out) = rejected.entry(*i_type).or_default();
+        let (keep, out) = std::mem::take(chaos)
+            .into_iter()
+            .partition(|item| rule.accepts(item));
+        *chaos = keep;
+        *chaos_out = out;
+        let (keep, out) = std::mem::take(regal)
+            .into_iter()
+            .partition(|item| rule.accepts(item));
+        *regal = keep;
+        *regal_out = out;
+    }
+    let map = &accepted;
+
+    let count_of = |i_type| {
+        let (chaos, regal) = lists(map, i_type);
+        chaos.len() + regal.len()
+    };
+    let chaos_items: usize = layout
+        .slots
+        .iter()
+        .map(|(i_type, _)| lists(map, *i_type).0.len())
+        .sum();
+    let (fixed, choice) = match layout.choice {
+        true => (&layout.slots[2..], Some((layout.slots[0], layout.slots[1]))),
+        false => (&layout.slots[..], None),
+    };
+    let upper = fixed
+        .iter()
+        .map(|(i_type, per_set)| count_of(*i_type) / per_set)
+        .chain(
+            choice.map(|((first, first_per_set), (second, second_per_set))| {
+                count_of(first) / first_per_set + count_of(second) / second_per_set
+            }),
+        )
+        .chain(std::iter::once(chaos_items))
+        .min()
+        .unwrap_or(0);
+
+    let mut built = None;
+    'counts: for count in (1..=upper).rev() {
+        let firsts = match layout.choice {
+            true => count,
+            false => 0,
+        };
+        for first in (0..=firsts).rev() {
+            if let Some(slots) = plan(map, &layout, count, first) {
+                built = Some(build(map, count, &slots));
+                break 'counts;
+            }
+        }
+    }
+    let (sets, mut remaining) = built.unwrap_or_else(|| (VecDeque::new(), map.clone()));
+    for (i_type, (chaos, regal)) in rejected {
+        let (chaos_list, regal_list) = remaining.entry(i_type).or_default();
+        chaos_list.extend(chaos);
+        regal_list.extend(regal);
+    }
+    Some((sets, remaining))
+}
+
+/// Decides how many chaos items each slot uses for `count` sets, `first` of which get the
+/// first option of the choice. Returns `None` if the sets can't be built that way.
+fn plan<'a>(
+    map: &'a ChaosRecipeSet,
+    layout: &Layout,
+    count: usize,
+    first: usize,
+) -> Option<Vec<Slot<'a>>> {
+    let mut slots: Vec<Slot> = layout
+        .slots
+        .iter()
+        .enumerate()
+        .map(|(idx, (i_type, per_set))| {
+            let (chaos, regal) = lists(map, *i_type);
+            let sets = match (layout.choice, idx) {
+                (true, 0) => 0..first,
+                (true, 1) => first..count,
+                _ => 0..count,
+            };
+            Slot {
+                i_type: *i_type,
+                per_set: *per_set,
+                sets,
+                chaos,
+                regal,
+                chaos_used: 0,
+            }
+        })
+        .collect();
+
+    // 리갈 아이템이 모자란 만큼은 카오스 아이템을 써야 한다.
+    for slot in slots.iter_mut() {
+        let needed = slot.needed();
+        if slot.chaos.len() + slot.regal.len() < needed {
+            return None;
+        }
+        slot.chaos_used = needed.saturating_sub(slot.regal.len());
+    }
+
+    let mut missing = count.saturating_sub(slots.iter().map(Slot::covered).sum());
+    for slot in slots.iter_mut() {
+        let spare = slot.chaos.len().min(slot.needed()) - slot.chaos_used;
+        let uncovered = slot.sets.len() - slot.covered();
+        let extra = spare.min(uncovered).min(missing);
+        slot.chaos_used += extra;
+        missing -= extra;
+    }
+    if missing > 0 {
+        return None;
+    }
+    Some(slots)
+}
+
+fn build(
+    map: &ChaosRecipeSet,
+    count: usize,
+    slots: &[Slot],
+) -> (VecDeque<Vec<Item>>, ChaosRecipeSet) {
+    let mut sets = vec![Vec::new(); count];
+    let mut weapons = vec![0; count];
+    let mut has_chaos = vec![false; count];
+    for slot in slots {
+        // 카오스 아이템이 아직 없는 세트부터 채운다.
+        let order: Vec<usize> = slot
+            .sets
+            .clone()
+            .filter(|set| !has_chaos[*set])
+            .chain(slot.sets.clone().filter(|set| has_chaos[*set]))
+            .collect();
+        let mut chaos = slot.chaos[..slot.chaos_used].iter();
+        let mut regal = slot.regal[..slot.needed() - slot.chaos_used].iter();
+        for _ in 0..slot.per_set {
+            for set in order.iter() {
+                let item = match chaos.next() {
+                    Some(item) => {
+                        has_chaos[*set] = true;
+                        item
+                    }
+                    None => regal.next().unwrap(),
+                };
+                sets[*set].push(item.clone());
+                if let ItemType::Weapon2H | ItemType::Weapon1HOrShield = slot.i_type {
+                    weapons[*set] += 1;
+                }
+            }
+        }
+    }
+    // 무기는 다른 아이템들 뒤에 둔다.
+    for (set, weapons) in sets.iter_mut().zip(weapons) {
+        set.rotate_left(weapons);
+    }
+
+    let mut remaining = map.clone();
+    for slot in slots {
+        if let Some((chaos, regal)) = remaining.get_mut(&slot.i_type) {
+            chaos.drain(..slot.chaos_used);
+            regal.drain(..slot.needed() - slot.chaos_used);
+        }
+    }
+    (sets.into_iter().collect(), remaining)
+}
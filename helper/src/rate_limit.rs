@@ -0,0 +1,166 @@
+use crate::{HelperError, Result};
+use reqwest::header::HeaderMap;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// One `hits:period:restriction` entry of an `X-Rate-Limit-<rule>` header.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RateLimitRule {
+    pub max_hits: usize,
+    pub period: Duration,
+    /// how long the site locks us out when the rule is broken
+    pub restriction: Duration,
+    /// hits the site had counted when it last answered
+    server_hits: usize,
+}
+
+/// Keeps requests to the stash API within the policies the site announces.
+///
+/// The policies are read from the `X-Rate-Limit-*` headers of every response. A request
+/// which would break one of them is delayed if the wait is short, and refused otherwise.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rules: Vec<RateLimitRule>,
+    requests: VecDeque<Instant>,
+    last_response: Option<Instant>,
+    blocked_until: Option<Instant>,
+    max_delay: Duration,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(Duration::from_secs(5))
+    }
+}
+
+impl RateLimiter {
+    /// Requests which have to wait longer than `max_delay` are refused.
+    pub fn new(max_delay: Duration) -> Self {
+        Self {
+            rules: Vec::new(),
+            requests: VecDeque::new(),
+            last_response: None,
+            blocked_until: None,
+            max_delay,
+        }
+    }
+
+    pub fn rules(&self) -> &[RateLimitRule] {
+        &self.rules
+    }
+
+    /// How long the next request has to wait, if it can't be sent at `now`.
+    pub fn wait_time(&self, now: Instant) -> Option<Duration> {
+        let mut until = self.blocked_until.filter(|until| *until > now);
+        for rule in self.rules.iter() {
+            let in_window: Vec<_> = self
+                .requests
+                .iter()
+                .filter(|time| now.duration_since(**time) < rule.period)
+                .collect();
+            // 사이트가 센 요청 중에는 다른 프로그램이 보낸 것도 있을 수 있다.
+            let server_hits = match self.last_response {
+                Some(time) if now.duration_since(time) < rule.period => rule.server_hits,
+                _ => 0,
+            };
+            if in_window.len().max(server_hits) < rule.max_hits {
+                continue;
+            }
+            let rule_until = if in_window.len() >= rule.max_hits {
+                *in_window[in_window.len() - rule.max_hits] + rule.period
+            } else {
+                self.last_response.unwrap() + rule.period
+            };
+            until = until.max(Some(rule_until));
+        }
+        until.map(|until| until - now)
+    }
+
+    /// Remaining cooldown right now.
+    pub fn cooldown(&self) -> Option<Duration> {
+        self.wait_time(Instant::now())
+    }
+
+    /// Waits until a request can be sent and counts it, or refuses it.
+    pub fn acquire(&mut self) -> Result<()> {
+        if let Some(wait) = self.wait_time(Instant::now()) {
+            if wait > self.max_delay {
+                return Err(HelperError::RateLimited {
+                    retry_after: Some(wait),
+                });
+            }
+            std::thread::sleep(wait);
+        }
+        let now = Instant::now();
+        let longest_period = self
+            .rules
+            .iter()
+            .map(|rule| rule.period)
+            .max()
+            .unwrap_or_default();
+        while let Some(time) = self.requests.front() {
+            if now.duration_since(*time) < longest_period {
+                break;
+            }
+            self.requests.pop_front();
+        }
+        self.requests.push_back(now);
+        Ok(())
+    }
+
+    /// Reads the policies and their current state from a response.
+    pub fn update(&mut self, headers: &HeaderMap, now: Instant) {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+
+        if let Some(rule_names) = header("X-Rate-Limit-Rules") {
+            let mut rules = Vec::new();
+            for name in rule_names.split(',').map(str::trim) {
+                let limits = header(&format!("X-Rate-Limit-{}", name))
+                    .map(parse_triples)
+                    .unwrap_or_default();
+                let states = header(&format!("X-Rate-Limit-{}-State", name))
+                    .map(parse_triples)
+                    .unwrap_or_default();
+                for (idx, (max_hits, period, restriction)) in limits.into_iter().enumerate() {
+                    let (server_hits, active) = states
+                        .get(idx)
+                        .map(|(hits, _, active)| (*hits, *active))
+                        .unwrap_or((0, 0));
+                    if active > 0 {
+                        self.block_until(now + Duration::from_secs(active));
+                    }
+                    rules.push(RateLimitRule {
+                        max_hits: max_hits as usize,
+                        period: Duration::from_secs(period),
+                        restriction: Duration::from_secs(restriction),
+                        server_hits: server_hits as usize,
+                    });
+                }
+            }
+            self.rules = rules;
+            self.last_response = Some(now);
+        }
+
+        if let Some(secs) = header("Retry-After").and_then(|value| value.trim().parse().ok()) {
+            self.block_until(now + Duration::from_secs(secs));
+        }
+    }
+
+    fn block_until(&mut self, until: Instant) {
+        self.blocked_until = self.blocked_until.max(Some(until));
+    }
+}
+
+/// Parses `a:b:c,a:b:c`, skipping malformed entries.
+fn parse_triples(value: &str) -> Vec<(u64, u64, u64)> {
+    value
+        .split(',')
+        .filter_map(|triple| {
+            let mut nums = triple.trim().split(':').map(|num| num.parse::<u64>().ok());
+            match (nums.next(), nums.next(), nums.next()) {
+                (Some(Some(a)), Some(Some(b)), Some(Some(c))) => Some((a, b, c)),
+                _ => None,
+            }
+        })
+        .collect()
+}
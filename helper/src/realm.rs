@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Formatter};
+
+/// Which game servers the account plays on. Each has its own web site and stash API.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum Realm {
+    International,
+    Xbox,
+    Sony,
+    /// 카카오게임즈 한국 서버
+    Kakao,
+    GarenaTaiwan,
+    /// Any site serving the same API, e.g. a local mock server. `realm` is sent as is.
+    Custom {
+        base_url: String,
+        realm: String,
+    },
+}
+
+impl Default for Realm {
+    /// Older save files were all made for the Korean servers.
+    fn default() -> Self {
+        Realm::Kakao
+    }
+}
+
+impl Realm {
+    /// Realms which can be picked in the settings.
+    pub const ALL: [Realm; 5] = [
+        Realm::International,
+        Realm::Xbox,
+        Realm::Sony,
+        Realm::Kakao,
+        Realm::GarenaTaiwan,
+    ];
+
+    pub fn base_url(&self) -> &str {
+        match self {
+            Realm::International | Realm::Xbox | Realm::Sony => "https://www.pathofexile.com",
+            Realm::Kakao => "https://poe.game.daum.net",
+            Realm::GarenaTaiwan => "https://web.poe.garena.tw",
+            Realm::Custom { base_url, .. } => base_url.trim_end_matches('/'),
+        }
+    }
+
+    /// Value of the `realm` query parameter.
+    pub fn realm_code(&self) -> &str {
+        match self {
+            Realm::Xbox => "xbox",
+            Realm::Sony => "sony",
+            Realm::International | Realm::Kakao | Realm::GarenaTaiwan => "pc",
+            Realm::Custom { realm, .. } => realm,
+        }
+    }
+
+    pub fn stash_url(&self) -> String {
+        format!("{}/character-window/get-stash-items", self.base_url())
+    }
+
+    /// The public league list of the official API. It lists the leagues of every realm,
+    /// and the Korean and Taiwanese servers play the same leagues as the PC realm.
+    pub fn league_url(&self) -> String {
+        match self {
+            Realm::Custom { .. } => format!("{}/api/leagues", self.base_url()),
+            _ => "http://api.pathofexile.com/leagues".to_owned(),
+        }
+    }
+}
+
+impl fmt::Display for Realm {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Realm::International => f.write_str("International (PC)"),
+            Realm::Xbox => f.write_str("International (Xbox)"),
+            Realm::Sony => f.write_str("International (PlayStation)"),
+            Realm::Kakao => f.write_str("Kakao (Korea)"),
+            Realm::GarenaTaiwan => f.write_str("Garena (Taiwan)"),
+            Realm::Custom { base_url, .. } => f.write_str(base_url),
+        }
+    }
+}
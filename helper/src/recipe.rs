@@ -0,0 +1,348 @@
+use crate::{Influence, Item, ItemType, RecipeMode, Result};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+lazy_static! {
+    static ref CHAOS_RULE: RecipeRule =
+        RecipeRule::from_json(include_str!("../recipes/chaos.json")).unwrap();
+    static ref REGAL_RULE: RecipeRule =
+        RecipeRule::from_json(include_str!("../recipes/regal.json")).unwrap();
+    static ref INFLUENCED_RULE: RecipeRule =
+        RecipeRule::from_json(include_str!("../recipes/influenced.json")).unwrap();
+}
+
+impl RecipeMode {
+    /// The shipped definition of the recipe.
+    pub fn rule(&self) -> &'static RecipeRule {
+        match self {
+            RecipeMode::Chaos => &CHAOS_RULE,
+            RecipeMode::Regal => &REGAL_RULE,
+            RecipeMode::Influenced => &INFLUENCED_RULE,
+        }
+    }
+}
+
+/// Rarity of an item, in the order of its `frameType`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Rarity {
+    Normal,
+    Magic,
+    #[default]
+    Rare,
+    Unique,
+}
+
+/// Item levels from `min` up to and including `max`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct IlvlBand {
+    #[serde(default)]
+    pub min: usize,
+    #[serde(default)]
+    pub max: Option<usize>,
+}
+
+impl IlvlBand {
+    pub fn contains(&self, ilvl: usize) -> bool {
+        ilvl >= self.min && !matches!(self.max, Some(max) if ilvl > max)
+    }
+}
+
+/// At least `count` items of every set have to be in `ilvl`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Requirement {
+    pub ilvl: IlvlBand,
+    #[serde(default = "one")]
+    pub count: usize,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Identification {
+    #[default]
+    Any,
+    Identified,
+    Unidentified,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum InfluenceRule {
+    #[default]
+    Any,
+    /// only items without influence
+    None,
+    /// every item shares one influence
+    Same,
+}
+
+/// `count` items of any of `types`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SlotItems {
+    pub types: Vec<ItemType>,
+    #[serde(default = "one")]
+    pub count: usize,
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SlotRule {
+    /// the first of the options the stash can fill, e.g. a 2H weapon or two 1H weapons
+    OneOf {
+        one_of: Vec<Vec<SlotItems>>,
+    },
+    Items(SlotItems),
+}
+
+/// A vendor recipe, read from a JSON file like `recipes/chaos.json`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RecipeRule {
+    pub name: String,
+    #[serde(default)]
+    pub rarity: Rarity,
+    /// item levels every item has to be in
+    #[serde(default)]
+    pub ilvl: IlvlBand,
+    #[serde(default)]
+    pub require: Vec<Requirement>,
+    #[serde(default)]
+    pub identified: Identification,
+    #[serde(default)]
+    pub influence: InfluenceRule,
+    pub slots: Vec<SlotRule>,
+}
+
+impl RecipeRule {
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Whether `item` may be put in a set of this recipe.
+    pub fn accepts(&self, item: &Item) -> bool {
+        item.frame_type == self.rarity as usize
+            && self.ilvl.contains(item.ilvl)
+            && match self.identified {
+                Identification::Any => true,
+                Identification::Identified => item.identified,
+                Identification::Unidentified => !item.identified,
+            }
+            && match self.influence {
+                InfluenceRule::Any => true,
+                InfluenceRule::None => item.influences().is_empty(),
+                InfluenceRule::Same => !item.influences().is_empty(),
+            }
+    }
+
+    /// Whether `set` pays out this recipe. Only the items are checked, not the slots.
+    pub fn follows(&self, set: &[Item]) -> bool {
+        let mut missing: Vec<usize> = self.require.iter().map(|req| req.count).collect();
+        for item in set {
+            count_towards(self, item, &mut missing);
+        }
+        set.iter().all(|item| self.accepts(item))
+            && missing.iter().all(|missing| *missing == 0)
+            && match self.influence {
+                InfluenceRule::Same => Influence::ALL
+                    .iter()
+                    .any(|influence| set.iter().all(|item| item.has_influence(*influence))),
+                _ => true,
+            }
+    }
+
+    /// Whether `item` counts towards one of the requirements.
+    pub fn is_required(&self, item: &Item) -> bool {
+        self.require
+            .iter()
+            .any(|requirement| requirement.ilvl.contains(item.ilvl))
+    }
+}
+
+/// Builds sets for any `RecipeRule`.
+///
+/// Slots are filled in the order of the rule. While a requirement is not met, items which
+/// count towards it are preferred, and afterwards the items which don't count towards any,
+/// to keep the scarce ones for later sets.
+#[derive(Clone)]
+pub struct RuleGenerator<'r> {
+    rule: &'r RecipeRule,
+    pool: HashMap<ItemType, Vec<Item>>,
+    rejected: Vec<Item>,
+}
+
+impl<'r> RuleGenerator<'r> {
+    pub fn new(rule: &'r RecipeRule, items: impl IntoIterator<Item = Item>) -> Self {
+        let mut pool: HashMap<ItemType, Vec<Item>> = HashMap::new();
+        let mut rejected = Vec::new();
+        for item in items {
+            if rule.accepts(&item) {
+                pool.entry(item.itype).or_default().push(item);
+            } else {
+                rejected.push(item);
+            }
+        }
+        Self {
+            rule,
+            pool,
+            rejected,
+        }
+    }
+
+    /// Items which have not been put in any set yet.
+    pub fn remaining(&self) -> Vec<Item> {
+        self.rejected
+            .iter()
+            .chain(self.pool.values().flatten())
+            .cloned()
+            .collect()
+    }
+
+    fn take_set(&mut self, influence: Option<Influence>) -> Option<Vec<Item>> {
+        let rule = self.rule;
+        let mut missing: Vec<usize> = rule.require.iter().map(|req| req.count).collect();
+        let mut set = Vec::new();
+        for slot in rule.slots.iter() {
+            match slot {
+                SlotRule::Items(items) => {
+                    self.take_items(items, influence, &mut missing, &mut set)?
+                }
+                SlotRule::OneOf { one_of } => {
+                    let mut found = false;
+                    for option in one_of {
+                        let backup = (self.pool.clone(), missing.clone(), set.len());
+                        if option.iter().all(|items| {
+                            self.take_items(items, influence, &mut missing, &mut set)
+                                .is_some()
+                        }) {
+                            found = true;
+                            break;
+                        }
+                        self.pool = backup.0;
+                        missing = backup.1;
+                        set.truncate(backup.2);
+                    }
+                    if !found {
+                        return None;
+                    }
+                }
+            }
+        }
+        self.fill_requirements(&mut set, &mut missing, influence)?;
+        Some(set)
+    }
+
+    fn take_items(
+        &mut self,
+        items: &SlotItems,
+        influence: Option<Influence>,
+        missing: &mut [usize],
+        set: &mut Vec<Item>,
+    ) -> Option<()> {
+        let rule = self.rule;
+        for _ in 0..items.count {
+            let is_missing = |item: &Item| {
+                rule.require
+                    .iter()
+                    .zip(missing.iter())
+                    .any(|(req, missing)| *missing > 0 && req.ilvl.contains(item.ilvl))
+            };
+            let candidates: Vec<(ItemType, usize)> = items
+                .types
+                .iter()
+                .flat_map(|i_type| {
+                    self.pool
+                        .get(i_type)
+                        .into_iter()
+                        .flat_map(|list| list.iter().enumerate())
+                        .filter(|(_, item)| has_influence(item, influence))
+                        .map(move |(idx, _)| (*i_type, idx))
+                })
+                .collect();
+            let item_at = |(i_type, idx): &(ItemType, usize)| &self.pool[i_type][*idx];
+            let (i_type, idx) = candidates
+                .iter()
+                .find(|pos| is_missing(item_at(pos)))
+                .or_else(|| {
+                    candidates
+                        .iter()
+                        .find(|pos| !rule.is_required(item_at(pos)))
+                })
+                .or_else(|| candidates.first())
+                .copied()?;
+
+            let item = self.pool.get_mut(&i_type).unwrap().remove(idx);
+            count_towards(rule, &item, missing);
+            set.push(item);
+        }
+        Some(())
+    }
+
+    /// Swaps items which count towards no requirement for ones which count towards the
+    /// missing requirements.
+    fn fill_requirements(
+        &mut self,
+        set: &mut [Item],
+        missing: &mut [usize],
+        influence: Option<Influence>,
+    ) -> Option<()> {
+        let rule = self.rule;
+        for req_idx in 0..missing.len() {
+            while missing[req_idx] > 0 {
+                let band = rule.require[req_idx].ilvl;
+                let (set_idx, pool_idx) = set.iter().enumerate().find_map(|(set_idx, item)| {
+                    if rule.is_required(item) {
+                        return None;
+                    }
+                    self.pool.get(&item.itype).and_then(|list| {
+                        list.iter()
+                            .position(|new| {
+                                band.contains(new.ilvl) && has_influence(new, influence)
+                            })
+                            .map(|pool_idx| (set_idx, pool_idx))
+                    })
+                })?;
+                let list = self.pool.get_mut(&set[set_idx].itype).unwrap();
+                let new = list.remove(pool_idx);
+                count_towards(rule, &new, missing);
+                list.push(std::mem::replace(&mut set[set_idx], new));
+            }
+        }
+        Some(())
+    }
+}
+
+fn has_influence(item: &Item, influence: Option<Influence>) -> bool {
+    match influence {
+        Some(influence) => item.has_influence(influence),
+        None => true,
+    }
+}
+
+fn count_towards(rule: &RecipeRule, item: &Item, missing: &mut [usize]) {
+    for (req, missing) in rule.require.iter().zip(missing.iter_mut()) {
+        if *missing > 0 && req.ilvl.contains(item.ilvl) {
+            *missing -= 1;
+        }
+    }
+}
+
+impl<'r> Iterator for RuleGenerator<'r> {
+    type Item = Vec<Item>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let influences: Vec<Option<Influence>> = match self.rule.influence {
+            InfluenceRule::Same => Influence::ALL.iter().copied().map(Some).collect(),
+            _ => vec![None],
+        };
+        // 세트를 다 채우지 못하면 꺼냈던 아이템들을 되돌린다.
+        for influence in influences {
+            let backup = self.pool.clone();
+            if let Some(set) = self.take_set(influence) {
+                return Some(set);
+            }
+            self.pool = backup;
+        }
+        None
+    }
+}
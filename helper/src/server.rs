@@ -0,0 +1,308 @@
+use crate::{HelperError, HelperSession, RecipeMode, Result, DEFAULT_REFRESH_TIMEOUT};
+use futures::executor::block_on;
+use serde::Serialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{spawn, JoinHandle};
+use std::time::Duration;
+
+mod websocket;
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest request line and headers read, a lot more than any client of the API sends.
+const MAX_HEAD_LEN: u64 = 8 * 1024;
+
+/// Serves the data of a session as JSON to other programs on the same machine.
+///
+/// - `GET /status`: `StashStatus` of the last fetch
+/// - `GET /sets`: every set left, as `ChaosRecipe`s
+/// - `POST /sets/next`: the set to pick up now, like the stash mask hotkey
+/// - `POST /refresh`: fetches the stash and answers with the new `StashStatus`
+/// - `GET /events`: a WebSocket sending every `StashEvent` of the session as a text message
+///
+/// `?recipe=regal` picks the recipe, chaos by default. Only connections from localhost are
+/// accepted, and only requests with a `Host` of localhost and no `Origin` of another site,
+/// so web pages can't use the API. The server stops when it is dropped.
+#[derive(Debug)]
+pub struct ApiServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ApiServer {
+    /// Listens on `127.0.0.1:port`. Port 0 picks a free port, see `local_addr`.
+    pub fn start(session: Arc<HelperSession>, port: u16) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            spawn(move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::Acquire) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let session = session.clone();
+                        spawn(move || handle_connection(stream, &session, addr.port()));
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            addr,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        // accept()에서 기다리는 스레드를 깨운다.
+        TcpStream::connect(self.addr).ok();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// A request line like `GET /sets?recipe=regal HTTP/1.1`, split up.
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    /// with lowercase names
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads the request line and the headers. Requests which are too long or don't come in time
+/// are `None`.
+fn read_request(stream: &TcpStream) -> Option<Request> {
+    let mut reader = BufReader::new(stream.take(MAX_HEAD_LEN));
+    let mut line = String::new();
+    reader.read_line(&mut line).ok()?;
+    // 줄이 끝나기 전에 한도에 닿았다.
+    if !line.ends_with('\n') {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let method = parts.next()?.to_owned();
+    let target = parts.next()?;
+    // 본문은 쓰지 않으므로 헤더까지만 읽는다.
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header).ok()?;
+        if !header.ends_with('\n') {
+            return None;
+        }
+        if header.trim().is_empty() {
+            break;
+        }
+        if let Some(idx) = header.find(':') {
+            let name = header[..idx].trim().to_ascii_lowercase();
+            headers.push((name, header[idx + 1..].trim().to_owned()));
+        }
+    }
+
+    let (path, query) = match target.find('?') {
+        Some(idx) => (&target[..idx], &target[idx + 1..]),
+        None => (target, ""),
+    };
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.find('=') {
+            Some(idx) => (
+                percent_decode(&pair[..idx]),
+                percent_decode(&pair[idx + 1..]),
+            ),
+            None => (percent_decode(pair), String::new()),
+        })
+        .collect();
+    Some(Request {
+        method,
+        path: path.trim_end_matches('/').to_owned(),
+        query,
+        headers,
+    })
+}
+
+/// Decodes `%63haos` to `chaos` and `+` to a space, like browsers encode queries.
+/// Broken escapes are kept as they are.
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let hex = bytes
+            .get(idx + 1..idx + 3)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[idx], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                idx += 3;
+                continue;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        idx += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+    suggestion: Option<&'static str>,
+}
+
+/// Whether `host` is this server by a name which always means this machine. Pages of other
+/// sites can point their own names at 127.0.0.1 but can't change the `Host` they send.
+fn is_local_host(host: &str, port: u16) -> bool {
+    let (name, host_port) = match host.rfind(':') {
+        Some(idx) => (&host[..idx], host[idx + 1..].parse().ok()),
+        None => (host, Some(80)),
+    };
+    matches!(name, "localhost" | "127.0.0.1") && host_port == Some(port)
+}
+
+fn handle_connection(mut stream: TcpStream, session: &HelperSession, port: u16) {
+    // 아무것도 보내지 않는 클라이언트가 스레드를 잡아 두지 못하게 한다.
+    if stream.set_read_timeout(Some(READ_TIMEOUT)).is_err() {
+        return;
+    }
+    let request = match read_request(&stream) {
+        Some(request) => request,
+        None => return,
+    };
+    let host = request.header("host").unwrap_or_default();
+    // 브라우저는 다른 사이트에서 보내는 요청에 Origin을 붙인다.
+    let other_site = match request.header("origin") {
+        Some(origin) => !is_local_host(origin.strip_prefix("http://").unwrap_or_default(), port),
+        None => false,
+    };
+    if !is_local_host(host, port) || other_site {
+        let body = ErrorBody {
+            error: format!("requests for '{}' are not served", host),
+            suggestion: None,
+        };
+        return respond(&mut stream, 403, &body);
+    }
+    let mode = match request.query.iter().find(|(key, _)| key == "recipe") {
+        Some((_, name)) => match RecipeMode::ALL
+            .iter()
+            .find(|mode| mode.as_ref().eq_ignore_ascii_case(name))
+        {
+            Some(mode) => *mode,
+            None => {
+                let body = ErrorBody {
+                    error: format!("unknown recipe '{}'", name),
+                    suggestion: None,
+                };
+                return respond(&mut stream, 400, &body);
+            }
+        },
+        None => RecipeMode::default(),
+    };
+
+    let result = match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/events") => {
+            let key = match request.header("sec-websocket-key") {
+                Some(key) => key,
+                None => {
+                    let body = ErrorBody {
+                        error: "/events is a WebSocket".to_owned(),
+                        suggestion: None,
+                    };
+                    return respond(&mut stream, 426, &body);
+                }
+            };
+            return websocket::serve_events(stream, key, session);
+        }
+        ("GET", "/status") => block_on(session.status(mode)).map(|status| to_json(&status)),
+        ("GET", "/sets") => block_on(session.sets(mode)).map(|sets| to_json(&sets)),
+        ("POST", "/sets/next") => block_on(session.next_set(mode)).map(|set| to_json(&set)),
+        ("POST", "/refresh") => {
+            block_on(session.refresh(mode, DEFAULT_REFRESH_TIMEOUT)).map(|status| to_json(&status))
+        }
+        (_, "/status") | (_, "/sets") | (_, "/sets/next") | (_, "/refresh") | (_, "/events") => {
+            let body = ErrorBody {
+                error: format!("{} is not allowed", request.method),
+                suggestion: None,
+            };
+            return respond(&mut stream, 405, &body);
+        }
+        (_, path) => {
+            let body = ErrorBody {
+                error: format!("no such path '{}'", path),
+                suggestion: None,
+            };
+            return respond(&mut stream, 404, &body);
+        }
+    };
+    match result {
+        Ok(json) => respond(&mut stream, 200, &json),
+        Err(e) => {
+            let status = match e {
+                HelperError::RateLimited { .. } => 429,
+                HelperError::InvalidSession | HelperError::PrivateProfile => 401,
+                _ => 502,
+            };
+            let body = ErrorBody {
+                error: e.to_string(),
+                suggestion: Some(e.suggestion()),
+            };
+            respond(&mut stream, status, &body)
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or(serde_json::Value::Null)
+}
+
+fn respond<T: Serialize>(stream: &mut TcpStream, status: u16, body: &T) {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        426 => "Upgrade Required",
+        429 => "Too Many Requests",
+        _ => "Bad Gateway",
+    };
+    let body = serde_json::to_string(body).unwrap_or_default();
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).ok();
+}
@@ -0,0 +1,163 @@
+use crate::HelperSession;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{Arc, Mutex};
+use std::thread::spawn;
+use std::time::Duration;
+
+/// Appended to the key of the client before hashing it, from RFC 6455.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// How often an idle connection is pinged, so closed ones are noticed.
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+const OP_TEXT: u8 = 0x1;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xA;
+
+/// Largest payload the client may send. `/events` only reads pings and closes.
+const MAX_PAYLOAD_LEN: u64 = 4096;
+/// Largest payload of a ping, pong or close, from RFC 6455.
+const MAX_CONTROL_LEN: u64 = 125;
+/// Status code of a close frame for a client breaking the protocol.
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+
+/// Finishes the handshake and forwards the events of `session` until either side closes.
+pub(super) fn serve_events(stream: TcpStream, key: &str, session: &HelperSession) {
+    let accept = base64::encode(
+        &sha1_smol::Sha1::from(format!("{}{}", key, ACCEPT_GUID))
+            .digest()
+            .bytes(),
+    );
+    let events = match session.subscribe() {
+        Ok(events) => events,
+        Err(_) => return,
+    };
+    let reader = match stream.try_clone() {
+        Ok(reader) => reader,
+        Err(_) => return,
+    };
+    // 요청을 읽을 때의 시간 제한을 푼다. 클라이언트는 한참 동안 아무것도 보내지 않는다.
+    if reader.set_read_timeout(None).is_err() {
+        return;
+    }
+    let writer = Arc::new(Mutex::new(stream));
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept
+    );
+    if writer
+        .lock()
+        .unwrap()
+        .write_all(handshake.as_bytes())
+        .is_err()
+    {
+        return;
+    }
+
+    let reader_writer = writer.clone();
+    spawn(move || read_frames(reader, &reader_writer));
+
+    loop {
+        let (opcode, payload) = match events.recv_timeout(PING_INTERVAL) {
+            Ok(event) => match serde_json::to_vec(&event) {
+                Ok(json) => (OP_TEXT, json),
+                Err(_) => continue,
+            },
+            Err(RecvTimeoutError::Timeout) => (OP_PING, Vec::new()),
+            Err(RecvTimeoutError::Disconnected) => (OP_CLOSE, Vec::new()),
+        };
+        let mut stream = writer.lock().unwrap();
+        // 상대가 연결을 닫았으면 쓰기가 실패한다.
+        if write_frame(&mut *stream, opcode, &payload).is_err() || opcode == OP_CLOSE {
+            stream.shutdown(Shutdown::Both).ok();
+            break;
+        }
+    }
+}
+
+/// Answers pings and closes from the client. Other messages are ignored.
+fn read_frames(mut reader: TcpStream, writer: &Mutex<TcpStream>) {
+    loop {
+        let (opcode, payload) = match read_frame(&mut reader) {
+            Ok(frame) => frame,
+            Err(e) if e.kind() == ErrorKind::InvalidData => {
+                let status = CLOSE_PROTOCOL_ERROR.to_be_bytes();
+                write_frame(&mut *writer.lock().unwrap(), OP_CLOSE, &status).ok();
+                break;
+            }
+            Err(_) => break,
+        };
+        let mut stream = writer.lock().unwrap();
+        match opcode {
+            OP_PING => {
+                write_frame(&mut *stream, OP_PONG, &payload).ok();
+            }
+            OP_CLOSE => {
+                write_frame(&mut *stream, OP_CLOSE, &payload).ok();
+                break;
+            }
+            _ => {}
+        }
+    }
+    writer.lock().unwrap().shutdown(Shutdown::Both).ok();
+}
+
+fn write_frame(stream: &mut impl Write, opcode: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame)
+}
+
+/// Reads one frame of the client. Frames which aren't masked or are too long are
+/// `ErrorKind::InvalidData`, and nothing of their payload is read.
+fn read_frame(stream: &mut impl Read) -> std::io::Result<(u8, Vec<u8>)> {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head)?;
+    let opcode = head[0] & 0x0F;
+    let len = match head[1] & 0x7F {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0; 8];
+            stream.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+    // 클라이언트가 보내는 프레임은 항상 가려져 있어야 한다.
+    if head[1] & 0x80 == 0 {
+        return Err(Error::new(ErrorKind::InvalidData, "unmasked frame"));
+    }
+    let max_len = match opcode & 0x8 != 0 {
+        true => MAX_CONTROL_LEN,
+        false => MAX_PAYLOAD_LEN,
+    };
+    if len > max_len {
+        return Err(Error::new(ErrorKind::InvalidData, "frame too long"));
+    }
+    let mut mask = [0; 4];
+    stream.read_exact(&mut mask)?;
+    let mut payload = vec![0; len as usize];
+    stream.read_exact(&mut payload)?;
+    for (idx, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[idx % 4];
+    }
+    Ok((opcode, payload))
+}
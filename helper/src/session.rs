@@ -0,0 +1,781 @@
+use crate::event::{self, StashEvent};
+use crate::{
+    classify_items, fit_sets, is_unidentified_set, recipe_queue, removed_items,
+    summarize_influences, tab, tabs_of_set, unclassified_items, AccountData, ChaosRecipeSet,
+    HelperError, InventoryGrid, InventoryLayout, Item, LiveSource, RecipeMode, ResponseFromNetwork,
+    Result, StashData, StashSource, StashTab,
+};
+use futures::channel::oneshot;
+use futures::executor::block_on;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+use std::thread::{spawn, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Where the network thread sends the answer to a request.
+type Reply<T> = oneshot::Sender<Result<T>>;
+
+/// How long `acquire_chaos_list` waits for the stash it asked for.
+pub const DEFAULT_REFRESH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Fetches scheduled with `schedule_refresh` start at least this long after the previous one.
+const SCHEDULED_REFRESH_GAP: Duration = Duration::from_secs(10);
+
+/// One account watched by its own network thread.
+///
+/// The session owns the account, the stash source with its client and rate limiter, and
+/// the sets built from the last fetch. Sessions don't share anything, so several of them
+/// can run side by side. The threads are stopped when the session is dropped.
+#[derive(Debug)]
+pub struct HelperSession {
+    account: Arc<RwLock<AccountData>>,
+    sender: Mutex<mpsc::Sender<InternalMessage>>,
+    network_thread: Option<JoinHandle<()>>,
+}
+
+impl HelperSession {
+    /// A session which reads the stash from the site of the account's realm.
+    pub fn new(account: AccountData) -> Self {
+        Self::with_source(account, LiveSource::default())
+    }
+
+    /// Same as `new`, but the network thread reads stash data from `source`.
+    pub fn with_source<S: StashSource + 'static>(account: AccountData, source: S) -> Self {
+        let account = Arc::new(RwLock::new(account));
+        let (sender, receiver) = mpsc::channel();
+        let network_thread = spawn(network_thread_func(
+            receiver,
+            sender.clone(),
+            account.clone(),
+            source,
+        ));
+        Self {
+            account,
+            sender: Mutex::new(sender),
+            network_thread: Some(network_thread),
+        }
+    }
+
+    pub fn account(&self) -> AccountData {
+        self.account.read().unwrap().clone()
+    }
+
+    /// The account of the session as it changes, for threads which shouldn't keep the
+    /// session alive.
+    pub(crate) fn shared_account(&self) -> Arc<RwLock<AccountData>> {
+        self.account.clone()
+    }
+
+    /// The next fetch is made for `new_account`.
+    pub fn set_account(&self, new_account: AccountData) {
+        let mut account = self.account.write().unwrap();
+        if *account == new_account {
+            return;
+        }
+        *account = new_account;
+    }
+
+    /// Sets are rebuilt to fit in `inventory`.
+    pub fn set_inventory(&self, inventory: InventoryGrid) -> Result<()> {
+        self.send(InternalMessage::SetInventory(inventory))
+    }
+
+    /// Metadata of every stash tab of `account`, for choosing tabs by name.
+    ///
+    /// The request is sent through the source of the session, so it counts against the
+    /// same rate limits as the fetches.
+    pub async fn tab_list(&self, account: &AccountData) -> Result<Vec<StashTab>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(InternalMessage::RequestTabList(account.clone(), sender))?;
+        receiver.await.unwrap_or(Err(HelperError::Disconnected))
+    }
+
+    /// Starts a new fetch of the stash and waits up to `timeout` for its result.
+    ///
+    /// If the fetch takes longer, the latest data is returned instead. `generation` and `age`
+    /// of the response tell which fetch the data came from and how old it is.
+    pub async fn refresh(
+        &self,
+        mode: RecipeMode,
+        timeout: Duration,
+    ) -> Result<ResponseFromNetwork> {
+        self.request(|sender| InternalMessage::RequestStashStatus(mode, timeout, sender))
+            .await
+    }
+
+    /// Fetches the stash in the background as soon as the rate limits allow it.
+    ///
+    /// Nothing waits for the result, subscribers see it as usual. Calls made while a fetch
+    /// is running or already scheduled are merged into it.
+    pub fn schedule_refresh(&self) -> Result<()> {
+        self.send(InternalMessage::ScheduleRefresh)
+    }
+
+    /// The set for `mode` to pick up now.
+    ///
+    /// It is the same set until a fetch shows that its items left the stash, so the stash
+    /// is fetched again in the background, like `schedule_refresh`. If only some of them
+    /// left, the response holds the rest of them.
+    pub async fn next_set(&self, mode: RecipeMode) -> Result<ResponseFromNetwork> {
+        self.request(|sender| InternalMessage::RequestChaosRecipe(mode, sender))
+            .await
+    }
+
+    /// Status of the last fetch for `mode`, without fetching again.
+    pub async fn status(&self, mode: RecipeMode) -> Result<ResponseFromNetwork> {
+        self.request(|sender| InternalMessage::RequestStatus(mode, sender))
+            .await
+    }
+
+    /// Every set for `mode` which is left, starting with the one of `next_set`.
+    pub async fn sets(&self, mode: RecipeMode) -> Result<Vec<ResponseFromNetwork>> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(InternalMessage::RequestSets(mode, sender))?;
+        receiver.await.unwrap_or(Err(HelperError::Disconnected))
+    }
+
+    /// Events of this session from now on. The first one is a `CountsChanged` with the
+    /// current counts. The subscription ends when the receiver is dropped.
+    pub fn subscribe(&self) -> Result<mpsc::Receiver<StashEvent>> {
+        let (sender, receiver) = mpsc::channel();
+        self.send(InternalMessage::Subscribe(sender))?;
+        Ok(receiver)
+    }
+
+    /// Blocking version of `tab_list`.
+    pub fn stash_tab_list(&self, account: &AccountData) -> Result<Vec<StashTab>> {
+        block_on(self.tab_list(account))
+    }
+
+    /// Blocking version of `refresh` with the default timeout if `requre_whole`, and of
+    /// `next_set` otherwise.
+    pub fn acquire_chaos_list(
+        &self,
+        requre_whole: bool,
+        mode: RecipeMode,
+    ) -> Result<ResponseFromNetwork> {
+        match requre_whole {
+            true => self.refresh_stash_status(mode, DEFAULT_REFRESH_TIMEOUT),
+            false => block_on(self.next_set(mode)),
+        }
+    }
+
+    /// Blocking version of `refresh`.
+    pub fn refresh_stash_status(
+        &self,
+        mode: RecipeMode,
+        timeout: Duration,
+    ) -> Result<ResponseFromNetwork> {
+        block_on(self.refresh(mode, timeout))
+    }
+
+    /// A way to ask for the data of the last fetch from another thread, which doesn't keep
+    /// the session alive.
+    pub(crate) fn status_requester(&self) -> StatusRequester {
+        StatusRequester(self.sender.lock().unwrap().clone())
+    }
+
+    fn send(&self, msg: InternalMessage) -> Result<()> {
+        self.sender
+            .lock()
+            .unwrap()
+            .send(msg)
+            .map_err(|_| HelperError::Disconnected)
+    }
+
+    async fn request(
+        &self,
+        msg: impl FnOnce(Reply<ResponseFromNetwork>) -> InternalMessage,
+    ) -> Result<ResponseFromNetwork> {
+        let (sender, receiver) = oneshot::channel();
+        self.send(msg(sender))?;
+        receiver.await.unwrap_or(Err(HelperError::Disconnected))
+    }
+}
+
+impl Drop for HelperSession {
+    fn drop(&mut self) {
+        self.send(InternalMessage::Shutdown).ok();
+        if let Some(network_thread) = self.network_thread.take() {
+            network_thread.join().ok();
+        }
+    }
+}
+
+/// Blocking `status` of a session, failing with `Disconnected` once it is dropped.
+pub(crate) struct StatusRequester(mpsc::Sender<InternalMessage>);
+
+impl StatusRequester {
+    pub(crate) fn status(&self, mode: RecipeMode) -> Result<ResponseFromNetwork> {
+        let (sender, receiver) = oneshot::channel();
+        self.0
+            .send(InternalMessage::RequestStatus(mode, sender))
+            .map_err(|_| HelperError::Disconnected)?;
+        block_on(receiver).unwrap_or(Err(HelperError::Disconnected))
+    }
+}
+
+/// Fetches every tab in `account` and tags their items with the tab they came from. The
+/// names of the tabs are returned along, where they are known.
+fn fetch_tabs<S: StashSource>(
+    source: &mut S,
+    account: &AccountData,
+) -> Result<(Vec<Item>, HashMap<usize, String>)> {
+    let mut items = Vec::new();
+    let mut names = HashMap::new();
+    for (tab_idx, name) in tab::resolve_tabs(source, account)? {
+        if let Some(name) = name {
+            names.insert(tab_idx, name);
+        }
+        let StashData {
+            items: tab_items,
+            quad_layout,
+            ..
+        } = source.fetch(account, tab_idx)?;
+        items.extend(tab_items.into_iter().map(|mut item| {
+            item.tab_idx = tab_idx;
+            item.quad_tab = quad_layout;
+            item
+        }));
+    }
+    Ok((items, names))
+}
+
+/// Names of `tabs` for showing, falling back to the index of a tab without a known name.
+fn names_of_tabs(tabs: &[usize], names: &HashMap<usize, String>) -> Vec<String> {
+    tabs.iter()
+        .map(|idx| match names.get(idx) {
+            Some(name) => name.clone(),
+            None => idx.to_string(),
+        })
+        .collect()
+}
+
+/// The answer for the set of `items`, the ones which haven't been picked up yet.
+fn set_response(
+    items: Vec<Item>,
+    mode: RecipeMode,
+    layout: Option<InventoryLayout>,
+    picked: Vec<Item>,
+    tab_names: &HashMap<usize, String>,
+) -> ResponseFromNetwork {
+    let tabs = tabs_of_set(&items);
+    ResponseFromNetwork::ChaosRecipe {
+        tab_names: names_of_tabs(&tabs, tab_names),
+        tabs,
+        items,
+        mode,
+        layout,
+        picked,
+    }
+}
+
+/// What a fetch found in the stash.
+struct Snapshot {
+    map: ChaosRecipeSet,
+    unclassified: Vec<Item>,
+    /// every item, for telling which ones left the stash
+    items: Vec<Item>,
+    /// account the stash was read for
+    account: AccountData,
+    /// names of the tabs read, where the tab list was requested
+    tab_names: HashMap<usize, String>,
+}
+
+/// Whether fetches for `a` and `b` read the same stash tabs.
+fn same_stash(a: &AccountData, b: &AccountData) -> bool {
+    a.account == b.account
+        && a.realm == b.realm
+        && a.league == b.league
+        && a.stash_tabs == b.stash_tabs
+}
+
+/// The set `next_set` answers with, until its items leave the stash.
+struct CurrentSet {
+    items: Vec<Item>,
+    /// league of the fetch the set was built from
+    league: String,
+    layout: Option<InventoryLayout>,
+    /// items which are not in the stash anymore
+    picked: Vec<Item>,
+}
+
+impl CurrentSet {
+    fn response(
+        &self,
+        mode: RecipeMode,
+        tab_names: &HashMap<usize, String>,
+    ) -> ResponseFromNetwork {
+        let (items, layout): (Vec<_>, Vec<_>) = self
+            .items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| !self.picked.contains(item))
+            .map(|(idx, item)| (item.clone(), self.layout.as_ref().map(|layout| layout[idx])))
+            .unzip();
+        set_response(
+            items,
+            mode,
+            layout.into_iter().collect(),
+            self.picked.clone(),
+            tab_names,
+        )
+    }
+}
+
+/// What the network thread knows about the stash.
+struct StashState {
+    map: ChaosRecipeSet,
+    /// items of the last fetch whose slot is unknown
+    unclassified: Vec<Item>,
+    queue_mode: RecipeMode,
+    /// the set being picked up, which is not in `chaos_queue`
+    current: Option<CurrentSet>,
+    chaos_queue: VecDeque<Vec<Item>>,
+    /// inventory layout of each set in `chaos_queue`
+    layouts: VecDeque<Option<InventoryLayout>>,
+    inventory: InventoryGrid,
+    /// generation of the fetch `map` came from, 0 before the first one
+    generation: u64,
+    /// account of the fetch `map` came from
+    account: Option<AccountData>,
+    /// names of the tabs of that fetch
+    tab_names: HashMap<usize, String>,
+    fetched_at: Option<Instant>,
+    /// when the source can be asked again, as of the last fetch
+    cooldown_until: Option<Instant>,
+}
+
+impl StashState {
+    fn new() -> Self {
+        Self {
+            map: ChaosRecipeSet::new(),
+            unclassified: Vec::new(),
+            queue_mode: RecipeMode::default(),
+            current: None,
+            chaos_queue: VecDeque::new(),
+            layouts: VecDeque::new(),
+            inventory: InventoryGrid::default(),
+            generation: 0,
+            account: None,
+            tab_names: HashMap::new(),
+            fetched_at: None,
+            cooldown_until: None,
+        }
+    }
+
+    /// Builds the sets again. The current set is kept if it is for `mode`.
+    fn set_mode(&mut self, mode: RecipeMode) {
+        if mode != self.queue_mode {
+            self.current = None;
+        }
+        self.queue_mode = mode;
+        let mut map = self.map.clone();
+        if let Some(current) = &self.current {
+            for (chaos_list, regal_list) in map.values_mut() {
+                chaos_list.retain(|item| !current.items.contains(item));
+                regal_list.retain(|item| !current.items.contains(item));
+            }
+        }
+        self.chaos_queue = recipe_queue(&map, mode);
+        self.layouts = fit_sets(&mut self.chaos_queue, &map, mode, &self.inventory);
+    }
+
+    /// The current set, which is the first one in the queue if there is none.
+    fn current_set(&mut self, mode: RecipeMode) -> ResponseFromNetwork {
+        if mode != self.queue_mode {
+            self.set_mode(mode);
+        }
+        if self.current.is_none() {
+            let league = match &self.account {
+                Some(account) => account.league.clone(),
+                None => String::new(),
+            };
+            self.current = self.chaos_queue.pop_front().map(|items| CurrentSet {
+                items,
+                league,
+                layout: self.layouts.pop_front().unwrap_or(Some(Vec::new())),
+                picked: Vec::new(),
+            });
+        }
+        match &self.current {
+            Some(current) => current.response(mode, &self.tab_names),
+            None => set_response(
+                Vec::new(),
+                mode,
+                Some(Vec::new()),
+                Vec::new(),
+                &self.tab_names,
+            ),
+        }
+    }
+
+    /// Sets left, counting the current one.
+    fn set_count(&self) -> usize {
+        self.chaos_queue.len() + self.current.iter().count()
+    }
+
+    /// Takes in the stash of a fetch, and works out what happened to the current set.
+    ///
+    /// If the fetch read another league or other tabs than the last one, the sets are built
+    /// anew, since the items of the current set didn't leave the stash.
+    fn update(&mut self, snapshot: Snapshot, generation: u64) -> Vec<StashEvent> {
+        let old_counts = event::counts(&self.map);
+        let old_sets = self.set_count();
+        self.map = snapshot.map;
+        self.unclassified = snapshot.unclassified;
+        self.generation = generation;
+        self.fetched_at = Some(Instant::now());
+
+        let mode = self.queue_mode;
+        let mut set_event = None;
+        if !matches!(&self.account, Some(account) if same_stash(account, &snapshot.account)) {
+            self.current = None;
+        }
+        if let Some(current) = &mut self.current {
+            let picked = removed_items(&current.items, &snapshot.items);
+            if picked.len() == current.items.len() {
+                set_event = Some(StashEvent::SetConsumed {
+                    mode,
+                    items: current.items.clone(),
+                    league: current.league.clone(),
+                    remaining: 0,
+                });
+                self.current = None;
+            } else {
+                // 다시 넣어 둔 아이템은 집은 것에서 빠진다.
+                if picked.iter().any(|item| !current.picked.contains(item)) {
+                    set_event = Some(StashEvent::SetPartlyPicked {
+                        mode,
+                        picked: picked.clone(),
+                        left: removed_items(&current.items, &picked),
+                    });
+                }
+                current.picked = picked;
+            }
+        }
+        self.account = Some(snapshot.account);
+        self.tab_names = snapshot.tab_names;
+        self.set_mode(mode);
+
+        let mut events = Vec::new();
+        if event::counts(&self.map) != old_counts {
+            events.push(self.counts_event());
+        }
+        if let Some(mut set_event) = set_event {
+            if let StashEvent::SetConsumed { remaining, .. } = &mut set_event {
+                *remaining = self.set_count();
+            }
+            events.push(set_event);
+        }
+        let sets = self.set_count();
+        if sets > old_sets {
+            events.push(StashEvent::SetAvailable { mode, sets });
+        }
+        events
+    }
+
+    fn status(&mut self, mode: RecipeMode) -> ResponseFromNetwork {
+        if mode != self.queue_mode {
+            self.set_mode(mode);
+        }
+        let now = Instant::now();
+        ResponseFromNetwork::StashStatus {
+            recipe_set: self.map.clone(),
+            sets: self.set_count(),
+            unidentified_sets: self
+                .chaos_queue
+                .iter()
+                .chain(self.current.iter().map(|current| &current.items))
+                .filter(|set| is_unidentified_set(set))
+                .count(),
+            mode,
+            influenced: summarize_influences(&self.map),
+            unclassified: self.unclassified.clone(),
+            cooldown: self
+                .cooldown_until
+                .filter(|until| *until > now)
+                .map(|until| until - now),
+            generation: self.generation,
+            age: self.fetched_at.map(|time| time.elapsed()),
+        }
+    }
+
+    fn counts_event(&self) -> StashEvent {
+        StashEvent::CountsChanged {
+            counts: event::counts(&self.map),
+            mode: self.queue_mode,
+            sets: self.set_count(),
+            generation: self.generation,
+        }
+    }
+}
+
+/// Sends `event` to every subscriber, and forgets the ones which are gone.
+fn publish(subscribers: &mut Vec<mpsc::Sender<StashEvent>>, event: StashEvent) {
+    subscribers.retain(|subscriber| subscriber.send(event.clone()).is_ok());
+}
+
+/// A caller waiting for the fetch of `generation` to finish.
+struct Waiter {
+    generation: u64,
+    deadline: Instant,
+    mode: RecipeMode,
+    sender: Reply<ResponseFromNetwork>,
+}
+
+/// Fetches the network thread started, and the one it plans to start.
+struct Fetches {
+    next_generation: u64,
+    /// generation of the last fetch which finished, whether it worked or not
+    done_generation: u64,
+    last_started: Option<Instant>,
+    scheduled: Option<Instant>,
+}
+
+impl Fetches {
+    fn new() -> Self {
+        Self {
+            next_generation: 1,
+            done_generation: 0,
+            last_started: None,
+            scheduled: None,
+        }
+    }
+
+    /// Starts a fetch now and returns its generation.
+    fn start(&mut self, fetch_send: &mpsc::Sender<FetchJob>) -> u64 {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        fetch_send.send(FetchJob::Stash(generation)).ok();
+        self.last_started = Some(Instant::now());
+        self.scheduled = None;
+        generation
+    }
+
+    /// Plans a fetch for when the source is ready, unless one is running or planned already.
+    fn schedule(&mut self, cooldown_until: Option<Instant>) {
+        let running = self.next_generation - 1 > self.done_generation;
+        if !running && self.scheduled.is_none() {
+            let after_gap = self.last_started.map(|time| time + SCHEDULED_REFRESH_GAP);
+            self.scheduled = Some(Instant::now()).max(cooldown_until).max(after_gap);
+        }
+    }
+}
+
+/// Work for the fetch thread, which is the only one touching the source.
+enum FetchJob {
+    Stash(u64),
+    TabList(AccountData, Reply<Vec<StashTab>>),
+}
+
+fn fetch_thread_func<S: StashSource + 'static>(
+    recv: mpsc::Receiver<FetchJob>,
+    network_sender: mpsc::Sender<InternalMessage>,
+    account: Arc<RwLock<AccountData>>,
+    mut source: S,
+) -> impl FnOnce() {
+    move || {
+        while let Ok(job) = recv.recv() {
+            // 밀린 요청들은 한 번만 가져와도 모두 만족된다.
+            let mut generation = None;
+            for job in std::iter::once(job).chain(recv.try_iter()) {
+                match job {
+                    FetchJob::Stash(gen) => generation = generation.max(Some(gen)),
+                    FetchJob::TabList(account, sender) => {
+                        sender.send(source.tab_list(&account)).ok();
+                    }
+                }
+            }
+            let generation = match generation {
+                Some(generation) => generation,
+                None => continue,
+            };
+            let account = account.read().unwrap().clone();
+            let result = fetch_tabs(&mut source, &account).map(|(items, tab_names)| Snapshot {
+                map: classify_items(items.clone()),
+                unclassified: unclassified_items(&items),
+                items,
+                account: account.clone(),
+                tab_names,
+            });
+            let cooldown_until = source.cooldown().map(|cooldown| Instant::now() + cooldown);
+            if network_sender
+                .send(InternalMessage::FetchDone(
+                    generation,
+                    result,
+                    cooldown_until,
+                ))
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+fn network_thread_func<S: StashSource + 'static>(
+    recv: mpsc::Receiver<InternalMessage>,
+    self_sender: mpsc::Sender<InternalMessage>,
+    account: Arc<RwLock<AccountData>>,
+    source: S,
+) -> impl FnOnce() {
+    move || {
+        let (fetch_send, fetch_recv) = mpsc::channel();
+        let fetch_thread = spawn(fetch_thread_func(fetch_recv, self_sender, account, source));
+
+        let mut state = StashState::new();
+        let mut fetches = Fetches::new();
+        let mut waiters: Vec<Waiter> = Vec::new();
+        let mut subscribers = Vec::new();
+
+        loop {
+            let wake_up = waiters
+                .iter()
+                .map(|waiter| waiter.deadline)
+                .chain(fetches.scheduled)
+                .min();
+            let msg = match wake_up {
+                Some(deadline) => {
+                    match recv.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                        Ok(msg) => Some(msg),
+                        Err(mpsc::RecvTimeoutError::Timeout) => None,
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    }
+                }
+                None => match recv.recv() {
+                    Ok(msg) => Some(msg),
+                    Err(_) => break,
+                },
+            };
+
+            match msg {
+                Some(InternalMessage::Shutdown) => break,
+                Some(InternalMessage::RequestChaosRecipe(mode, sender)) => {
+                    sender.send(Ok(state.current_set(mode))).ok();
+                    // 세트를 팔았는지는 창고를 다시 봐야 알 수 있다.
+                    fetches.schedule(state.cooldown_until);
+                }
+                Some(InternalMessage::RequestStatus(mode, sender)) => {
+                    sender.send(Ok(state.status(mode))).ok();
+                }
+                Some(InternalMessage::RequestSets(mode, sender)) => {
+                    if mode != state.queue_mode {
+                        state.set_mode(mode);
+                    }
+                    let tab_names = &state.tab_names;
+                    let current = state
+                        .current
+                        .as_ref()
+                        .map(|current| current.response(mode, tab_names));
+                    let sets = current
+                        .into_iter()
+                        .chain(state.chaos_queue.iter().zip(state.layouts.iter()).map(
+                            |(items, layout)| {
+                                set_response(
+                                    items.clone(),
+                                    mode,
+                                    layout.clone(),
+                                    Vec::new(),
+                                    tab_names,
+                                )
+                            },
+                        ))
+                        .collect();
+                    sender.send(Ok(sets)).ok();
+                }
+                Some(InternalMessage::RequestStashStatus(mode, timeout, sender)) => {
+                    let generation = fetches.start(&fetch_send);
+                    waiters.push(Waiter {
+                        generation,
+                        deadline: Instant::now() + timeout,
+                        mode,
+                        sender,
+                    });
+                }
+                Some(InternalMessage::ScheduleRefresh) => fetches.schedule(state.cooldown_until),
+                Some(InternalMessage::SetInventory(inventory)) => {
+                    state.inventory = inventory;
+                    let mode = state.queue_mode;
+                    state.set_mode(mode);
+                }
+                Some(InternalMessage::Subscribe(subscriber)) => {
+                    // 끊긴 구독자는 다음 이벤트 때 지워진다.
+                    subscriber.send(state.counts_event()).ok();
+                    subscribers.push(subscriber);
+                }
+                Some(InternalMessage::RequestTabList(account, sender)) => {
+                    fetch_send.send(FetchJob::TabList(account, sender)).ok();
+                }
+                Some(InternalMessage::FetchDone(generation, result, cooldown_until)) => {
+                    fetches.done_generation = generation;
+                    state.cooldown_until = cooldown_until;
+                    let (done, pending): (Vec<_>, Vec<_>) = waiters
+                        .into_iter()
+                        .partition(|waiter| waiter.generation <= generation);
+                    waiters = pending;
+                    match result {
+                        Ok(snapshot) => {
+                            for event in state.update(snapshot, generation) {
+                                publish(&mut subscribers, event);
+                            }
+                        }
+                        // 쿨다운 중에는 이전 데이터와 함께 남은 시간을 알려준다.
+                        Err(HelperError::RateLimited { .. }) => {}
+                        Err(e) => {
+                            if let Some(event) = StashEvent::auth_error(&e) {
+                                publish(&mut subscribers, event);
+                            }
+                            for waiter in done {
+                                waiter.sender.send(Err(e.clone())).ok();
+                            }
+                            continue;
+                        }
+                    }
+                    for waiter in done {
+                        waiter.sender.send(Ok(state.status(waiter.mode))).ok();
+                    }
+                }
+                None => {}
+            }
+
+            let now = Instant::now();
+            if matches!(fetches.scheduled, Some(time) if time <= now) {
+                fetches.start(&fetch_send);
+            }
+
+            // 기다리다 지친 요청에는 지금 가진 데이터로 답한다.
+            let (expired, pending): (Vec<_>, Vec<_>) = waiters
+                .into_iter()
+                .partition(|waiter| waiter.deadline <= now);
+            waiters = pending;
+            for waiter in expired {
+                waiter.sender.send(Ok(state.status(waiter.mode))).ok();
+            }
+        }
+
+        // 진행 중인 요청이 끝나면 가져오는 스레드도 멈춘다.
+        drop(fetch_send);
+        fetch_thread.join().ok();
+    }
+}
+
+enum InternalMessage {
+    /// answer with the set to pick up now
+    RequestChaosRecipe(RecipeMode, Reply<ResponseFromNetwork>),
+    /// fetch the stash again and answer within the timeout
+    RequestStashStatus(RecipeMode, Duration, Reply<ResponseFromNetwork>),
+    /// answer with the data of the last fetch
+    RequestStatus(RecipeMode, Reply<ResponseFromNetwork>),
+    RequestSets(RecipeMode, Reply<Vec<ResponseFromNetwork>>),
+    RequestTabList(AccountData, Reply<Vec<StashTab>>),
+    SetInventory(InventoryGrid),
+    Subscribe(mpsc::Sender<StashEvent>),
+    /// fetch the stash when the rate limits allow it, with nobody waiting for it
+    ScheduleRefresh,
+    /// the fetch thread finished the fetch of a generation, and the source is cooling down
+    /// until the given time
+    FetchDone(u64, Result<Snapshot>, Option<Instant>),
+    /// the session was dropped
+    Shutdown,
+}
@@ -0,0 +1,225 @@
+use crate::{
+    classify_items, is_unidentified_set, optimal_chaos_sets, ChaosListGenerator, Influence, Item,
+    ItemType, RecipeRule, RuleGenerator, SlotItems, SlotRule,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Items of a synthetic stash, read from a fixture like `tests/fixtures/simulate/*.json`.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StashSpec {
+    pub items: Vec<ItemSpec>,
+}
+
+/// `count` items of the same type, level and size.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ItemSpec {
+    #[serde(rename = "type")]
+    pub item_type: ItemType,
+    pub ilvl: usize,
+    #[serde(default = "one")]
+    pub count: usize,
+    /// the usual size of `item_type` if not given
+    #[serde(default)]
+    pub w: Option<usize>,
+    #[serde(default)]
+    pub h: Option<usize>,
+    #[serde(default = "yes")]
+    pub identified: bool,
+    #[serde(default)]
+    pub influences: Vec<Influence>,
+}
+
+fn one() -> usize {
+    1
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl StashSpec {
+    pub fn from_json(json: &str) -> crate::Result<Self> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Builds the items, each at its own position so no two of them are equal.
+    pub fn items(&self) -> Vec<Item> {
+        let mut items = Vec::new();
+        for spec in self.items.iter() {
+            let (w, h) = usual_size(spec.item_type);
+            for _ in 0..spec.count {
+                let idx = items.len();
+                items.push(Item {
+                    id: None,
+                    w: spec.w.unwrap_or(w),
+                    h: spec.h.unwrap_or(h),
+                    x: idx % 24,
+                    y: idx / 24,
+                    ilvl: spec.ilvl,
+                    frame_type: 2,
+                    identified: spec.identified,
+                    influences: spec.influences.clone(),
+                    base_type: usual_base(spec.item_type).to_owned(),
+                    tab_idx: 0,
+                    quad_tab: true,
+                    itype: spec.item_type,
+                });
+            }
+        }
+        items
+    }
+}
+
+fn usual_size(item_type: ItemType) -> (usize, usize) {
+    match item_type {
+        ItemType::Weapon2H => (2, 4),
+        ItemType::Weapon1HOrShield | ItemType::Body => (2, 3),
+        ItemType::Helmet | ItemType::Boots | ItemType::Gloves => (2, 2),
+        ItemType::Belt => (2, 1),
+        _ => (1, 1),
+    }
+}
+
+fn usual_base(item_type: ItemType) -> &'static str {
+    match item_type {
+        ItemType::Weapon1HOrShield => "Imperial Claw",
+        ItemType::Weapon2H => "Judgement Staff",
+        ItemType::Body => "Astral Plate",
+        ItemType::Helmet => "Hubris Circlet",
+        ItemType::Boots => "Slink Boots",
+        ItemType::Gloves => "Vaal Gauntlets",
+        ItemType::Ring => "Two-Stone Ring",
+        ItemType::Amulet => "Coral Amulet",
+        ItemType::Belt => "Leather Belt",
+        ItemType::Useless => "Light Quiver",
+        ItemType::Unclassified => "Unknown Item",
+    }
+}
+
+/// Which code builds the sets.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Generator {
+    /// `ChaosListGenerator`, chaos sets only
+    Greedy,
+    /// `optimal_chaos_sets`, chaos sets only
+    Optimal,
+    /// `RuleGenerator` with the rule the sets are checked against
+    Rule,
+}
+
+impl Generator {
+    pub const ALL: [Generator; 3] = [Generator::Greedy, Generator::Optimal, Generator::Rule];
+}
+
+/// A way a set breaks the rule it was built for. `set` is the index of the set.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Violation {
+    /// the item types don't fill the slots of the rule
+    Slots { set: usize },
+    /// an item isn't accepted or a requirement isn't met
+    Rule { set: usize },
+    /// an item is used more often than it is in the stash
+    Reused { set: usize },
+}
+
+/// What a generator made of a stash.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SimulationReport {
+    pub sets: usize,
+    pub unidentified_sets: usize,
+    /// items in no set, per type
+    pub leftovers: HashMap<ItemType, usize>,
+    pub violations: Vec<Violation>,
+}
+
+/// Runs `generator` on `items` and checks every set against `rule`.
+pub fn simulate(items: &[Item], rule: &RecipeRule, generator: Generator) -> SimulationReport {
+    let sets: Vec<Vec<Item>> = match generator {
+        Generator::Greedy => ChaosListGenerator::new(&classify_items(items.to_vec())).collect(),
+        Generator::Optimal => optimal_chaos_sets(&classify_items(items.to_vec()))
+            .0
+            .into_iter()
+            .collect(),
+        Generator::Rule => RuleGenerator::new(rule, items.to_vec()).collect(),
+    };
+
+    let mut unused = items.to_vec();
+    let mut violations = Vec::new();
+    for (idx, set) in sets.iter().enumerate() {
+        if !fills_slots(set, &rule.slots) {
+            violations.push(Violation::Slots { set: idx });
+        }
+        if !rule.follows(set) {
+            violations.push(Violation::Rule { set: idx });
+        }
+        for item in set {
+            match unused.iter().position(|unused| unused == item) {
+                Some(pos) => {
+                    unused.swap_remove(pos);
+                }
+                None => {
+                    violations.push(Violation::Reused { set: idx });
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut leftovers = HashMap::new();
+    for item in unused.iter() {
+        *leftovers.entry(item.itype).or_default() += 1;
+    }
+    SimulationReport {
+        sets: sets.len(),
+        unidentified_sets: sets.iter().filter(|set| is_unidentified_set(set)).count(),
+        leftovers,
+        violations,
+    }
+}
+
+/// Whether the item types of `set` are exactly the ones `slots` ask for.
+fn fills_slots(set: &[Item], slots: &[SlotRule]) -> bool {
+    let mut counts: HashMap<ItemType, usize> = HashMap::new();
+    for item in set {
+        *counts.entry(item.itype).or_default() += 1;
+    }
+    for slot in slots {
+        match slot {
+            SlotRule::Items(items) => {
+                if !take(&mut counts, items) {
+                    return false;
+                }
+            }
+            SlotRule::OneOf { one_of } => {
+                let found = one_of.iter().any(|option| {
+                    let mut option_counts = counts.clone();
+                    if option.iter().all(|items| take(&mut option_counts, items)) {
+                        counts = option_counts;
+                        true
+                    } else {
+                        false
+                    }
+                });
+                if !found {
+                    return false;
+                }
+            }
+        }
+    }
+    counts.values().all(|count| *count == 0)
+}
+
+fn take(counts: &mut HashMap<ItemType, usize>, items: &SlotItems) -> bool {
+    for _ in 0..items.count {
+        match items
+            .types
+            .iter()
+            .find(|i_type| counts.get(i_type).copied().unwrap_or(0) > 0)
+        {
+            Some(i_type) => *counts.get_mut(i_type).unwrap() -= 1,
+            None => return false,
+        }
+    }
+    true
+}
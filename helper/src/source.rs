@@ -0,0 +1,166 @@
+use crate::{AccountData, HelperError, RateLimiter, Result, StashData, StashTab};
+use reqwest::Client;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Where the network thread gets the contents of a stash tab from.
+pub trait StashSource: Send {
+    fn fetch(&mut self, account: &AccountData, tab_idx: usize) -> Result<StashData>;
+
+    /// Metadata of every stash tab of the account.
+    fn tab_list(&mut self, account: &AccountData) -> Result<Vec<StashTab>> {
+        self.fetch(account, 0).map(|data| data.tabs)
+    }
+
+    /// Time left until the source can be asked again.
+    fn cooldown(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Asks the site of the account's realm for the stash tab of the account.
+///
+/// Every request goes through the `RateLimiter` of the source.
+#[derive(Debug)]
+pub struct LiveSource {
+    client: Client,
+    limiter: RateLimiter,
+}
+
+impl Default for LiveSource {
+    fn default() -> Self {
+        Self::new(RateLimiter::default())
+    }
+}
+
+impl LiveSource {
+    pub fn new(limiter: RateLimiter) -> Self {
+        Self {
+            client: Client::new(),
+            limiter,
+        }
+    }
+
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    fn request(
+        &mut self,
+        account: &AccountData,
+        tab_idx: usize,
+        with_tabs: bool,
+    ) -> Result<StashData> {
+        self.limiter.acquire()?;
+        let mut res = self
+            .client
+            .get(&account.realm.stash_url())
+            .query(&[
+                ("accountName", account.account.as_str()),
+                ("realm", account.realm.realm_code()),
+                ("league", account.league.as_str()),
+            ])
+            .query(&[("tabs", with_tabs as u8)])
+            .query(&[("tabIndex", tab_idx)])
+            .query(&[("public", false)])
+            .header("Cookie", account.cookie.as_str())
+            .send()?;
+        self.limiter.update(res.headers(), Instant::now());
+        let status = res.status();
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs);
+        let body = res.text()?;
+        if status.is_success() {
+            match serde_json::from_str(&body) {
+                Ok(data) => return Ok(data),
+                Err(e) if !body.contains("\"error\"") => return Err(e.into()),
+                Err(_) => {}
+            }
+        }
+        Err(HelperError::from_response(
+            status.as_u16(),
+            &body,
+            retry_after,
+            &account.league,
+            tab_idx,
+            !account.cookie.is_empty(),
+        ))
+    }
+}
+
+impl StashSource for LiveSource {
+    fn fetch(&mut self, account: &AccountData, tab_idx: usize) -> Result<StashData> {
+        self.request(account, tab_idx, false)
+    }
+
+    fn tab_list(&mut self, account: &AccountData) -> Result<Vec<StashTab>> {
+        self.request(account, 0, true).map(|data| data.tabs)
+    }
+
+    fn cooldown(&self) -> Option<Duration> {
+        self.limiter.cooldown()
+    }
+}
+
+/// Reads a captured `get-stash-items` response from a file on every fetch.
+///
+/// If the path is a directory, tab `n` is read from `<path>/<n>.json`.
+#[derive(Debug, Clone)]
+pub struct FileSource {
+    path: PathBuf,
+}
+
+impl FileSource {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl StashSource for FileSource {
+    fn fetch(&mut self, _account: &AccountData, tab_idx: usize) -> Result<StashData> {
+        let path = if self.path.is_dir() {
+            self.path.join(format!("{}.json", tab_idx))
+        } else {
+            self.path.clone()
+        };
+        let file = std::fs::File::open(&path)?;
+        Ok(serde_json::from_reader(std::io::BufReader::new(file))?)
+    }
+}
+
+/// Hands out the same stash data on every fetch, unless a tab has its own data.
+#[derive(Debug, Clone)]
+pub struct MemorySource {
+    data: StashData,
+    tabs: HashMap<usize, StashData>,
+}
+
+impl MemorySource {
+    pub fn new(data: StashData) -> Self {
+        Self {
+            data,
+            tabs: HashMap::new(),
+        }
+    }
+
+    pub fn with_tab(mut self, tab_idx: usize, data: StashData) -> Self {
+        self.tabs.insert(tab_idx, data);
+        self
+    }
+
+    /// `json` has the same shape as a `get-stash-items` response.
+    pub fn from_json(json: &str) -> Result<Self> {
+        Ok(Self::new(serde_json::from_str(json)?))
+    }
+}
+
+impl StashSource for MemorySource {
+    fn fetch(&mut self, _account: &AccountData, tab_idx: usize) -> Result<StashData> {
+        Ok(self.tabs.get(&tab_idx).unwrap_or(&self.data).clone())
+    }
+}
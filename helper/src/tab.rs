@@ -0,0 +1,119 @@
+use crate::{AccountData, HelperError, Result, StashSource};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Formatter};
+
+/// Metadata of a stash tab, as sent with `tabs=1`.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct StashTab {
+    #[serde(rename = "n")]
+    pub name: String,
+    #[serde(rename = "i")]
+    pub index: usize,
+    pub id: String,
+    #[serde(rename = "type")]
+    pub tab_type: String,
+    #[serde(default)]
+    pub colour: TabColour,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TabColour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl fmt::Display for StashTab {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.name, self.index)
+    }
+}
+
+/// A stash tab chosen by the user.
+///
+/// A tab picked from the tab list is remembered by its id and name, so it is still found
+/// after the tabs are reordered. Bare indices come from older save files.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum TabSelector {
+    Index(usize),
+    Tab { id: String, name: String },
+}
+
+impl TabSelector {
+    pub fn is_tab(&self, tab: &StashTab) -> bool {
+        match self {
+            TabSelector::Index(idx) => *idx == tab.index,
+            TabSelector::Tab { id, .. } => *id == tab.id,
+        }
+    }
+
+    fn find_in<'a>(&self, tabs: &'a [StashTab]) -> Option<&'a StashTab> {
+        match self {
+            TabSelector::Index(idx) => tabs.iter().find(|tab| tab.index == *idx),
+            // 탭을 지웠다 다시 만들면 id가 바뀌므로 이름으로도 찾아본다.
+            TabSelector::Tab { id, name } => tabs
+                .iter()
+                .find(|tab| tab.id == *id)
+                .or_else(|| tabs.iter().find(|tab| tab.name == *name)),
+        }
+    }
+}
+
+impl From<&StashTab> for TabSelector {
+    fn from(tab: &StashTab) -> Self {
+        TabSelector::Tab {
+            id: tab.id.clone(),
+            name: tab.name.clone(),
+        }
+    }
+}
+
+impl fmt::Display for TabSelector {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            TabSelector::Index(idx) => write!(f, "{}", idx),
+            TabSelector::Tab { name, .. } => f.write_str(name),
+        }
+    }
+}
+
+/// Turns the tabs chosen in `account` into the current tab indices, with the names of the
+/// tabs if the tab list was requested.
+///
+/// The tab list is only requested when a tab was chosen by name. The first tab is scanned
+/// when nothing is chosen.
+pub(crate) fn resolve_tabs<S: StashSource>(
+    source: &mut S,
+    account: &AccountData,
+) -> Result<Vec<(usize, Option<String>)>> {
+    if account.stash_tabs.is_empty() {
+        return Ok(vec![(0, None)]);
+    }
+    if let Some(indices) = account
+        .stash_tabs
+        .iter()
+        .map(|selector| match selector {
+            TabSelector::Index(idx) => Some((*idx, None)),
+            TabSelector::Tab { .. } => None,
+        })
+        .collect::<Option<Vec<_>>>()
+    {
+        return Ok(indices);
+    }
+
+    let tab_list = source.tab_list(account)?;
+    account
+        .stash_tabs
+        .iter()
+        .map(|selector| {
+            selector
+                .find_in(&tab_list)
+                .map(|tab| (tab.index, Some(tab.name.clone())))
+                .ok_or_else(|| match selector {
+                    TabSelector::Index(idx) => HelperError::TabOutOfRange(*idx),
+                    TabSelector::Tab { name, .. } => HelperError::TabNotFound(name.clone()),
+                })
+        })
+        .collect()
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{item, TIMEOUT};
+use helper::{
+    AccountData, AreaKind, ClientLog, HelperSession, LogEvent, LogWatcher, MemorySource,
+    RecipeMode, ResponseFromNetwork, StashData, StashEvent,
+};
+use serde_json::json;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A copy of the fixture log the test can append to.
+fn copy_of_log(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("chaos_helper_{}_{}.txt", name, std::process::id()));
+    fs::copy("tests/fixtures/client_log/Client.txt", &path).unwrap();
+    path
+}
+
+fn append(path: &PathBuf, text: &str) {
+    let mut file = OpenOptions::new().append(true).open(path).unwrap();
+    file.write_all(text.as_bytes()).unwrap();
+}
+
+fn entered(area: &str, kind: AreaKind) -> LogEvent {
+    LogEvent::AreaEntered {
+        area: area.to_owned(),
+        kind,
+    }
+}
+
+#[test]
+fn parses_area_entries() {
+    let parse = |message: &str| {
+        LogEvent::parse(&format!(
+            "2021/01/18 20:10:07 3015 2c7 [INFO Client 7220] {}",
+            message
+        ))
+    };
+    assert_eq!(
+        parse(": You have entered Celestial Hideout."),
+        Some(entered("Celestial Hideout", AreaKind::Hideout))
+    );
+    assert_eq!(
+        parse(": You have entered The Rogue Harbour."),
+        Some(entered("The Rogue Harbour", AreaKind::Town))
+    );
+    assert_eq!(
+        parse(": You have entered Strand.\r"),
+        Some(entered("Strand", AreaKind::Other))
+    );
+    assert_eq!(
+        parse(": 천상의 은신처에 진입했습니다."),
+        Some(entered("천상의 은신처", AreaKind::Hideout))
+    );
+    assert!(!parse(": You have entered Strand.").unwrap().wants_refresh());
+    assert!(parse(": You have entered Oriath.").unwrap().wants_refresh());
+
+    // 채팅은 무시한다.
+    assert_eq!(parse("@From Trader: You have entered Oriath."), None);
+    assert_eq!(parse("#Exile: : You have entered Oriath"), None);
+    assert_eq!(
+        parse("Connecting to instance server at 127.0.0.1:6112"),
+        None
+    );
+}
+
+#[test]
+fn reads_only_new_lines() {
+    let path = copy_of_log("new_lines");
+    let mut log = ClientLog::open(&path).unwrap();
+    assert_eq!(log.poll().unwrap(), Vec::new());
+
+    append(
+        &path,
+        "2021/01/18 20:15:00 300000 2c7 [INFO Client 7220] : You have entered Oriath.\n\
+         2021/01/18 20:15:30 330000 2c7 [INFO Client 7220] : You have entered Celes",
+    );
+    assert_eq!(log.poll().unwrap(), vec![entered("Oriath", AreaKind::Town)]);
+    // 반쯤 쓰인 줄은 끝날 때까지 기다린다.
+    append(&path, "tial Hideout.\r\n");
+    assert_eq!(
+        log.poll().unwrap(),
+        vec![entered("Celestial Hideout", AreaKind::Hideout)]
+    );
+    assert_eq!(log.poll().unwrap(), Vec::new());
+
+    // 게임이 로그를 새로 만들면 처음부터 읽는다.
+    fs::write(
+        &path,
+        "2021/01/19 10:00:00 1000 2c7 [INFO Client 1] : You have entered Highgate.\n",
+    )
+    .unwrap();
+    assert_eq!(
+        log.poll().unwrap(),
+        vec![entered("Highgate", AreaKind::Town)]
+    );
+    fs::remove_file(&path).ok();
+}
+
+fn stash() -> MemorySource {
+    MemorySource::new(StashData {
+        items: vec![
+            item(json!({"x": 0, "ilvl": 70, "baseType": "Coral Amulet"})),
+            item(json!({"x": 1, "baseType": "Leather Belt"})),
+        ],
+        quad_layout: false,
+        tabs: Vec::new(),
+    })
+}
+
+fn generation(session: &HelperSession) -> u64 {
+    match futures::executor::block_on(session.status(RecipeMode::Chaos)).unwrap() {
+        ResponseFromNetwork::StashStatus { generation, .. } => generation,
+        other => panic!("expected a status, got {:?}", other),
+    }
+}
+
+#[test]
+fn entering_a_hideout_refreshes_the_stash() {
+    let path = copy_of_log("watcher");
+    let session = Arc::new(HelperSession::with_source(AccountData::default(), stash()));
+    let events = session.subscribe().unwrap();
+    events.recv_timeout(TIMEOUT).unwrap();
+    let _watcher = LogWatcher::start(&path, session.clone(), Duration::from_millis(10)).unwrap();
+
+    append(
+        &path,
+        "2021/01/18 20:15:00 300000 2c7 [INFO Client 7220] : You have entered Strand.\n",
+    );
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(generation(&session), 0);
+
+    append(
+        &path,
+        "2021/01/18 20:20:00 600000 2c7 [INFO Client 7220] : You have entered Celestial Hideout.\n",
+    );
+    match events.recv_timeout(TIMEOUT).unwrap() {
+        StashEvent::CountsChanged { generation, .. } => assert_eq!(generation, 1),
+        other => panic!("expected new counts, got {:?}", other),
+    }
+
+    // 바로 다시 들어가도 곧장 가져오지 않는다.
+    append(
+        &path,
+        "2021/01/18 20:20:05 605000 2c7 [INFO Client 7220] : You have entered Oriath.\n",
+    );
+    std::thread::sleep(Duration::from_millis(100));
+    assert_eq!(generation(&session), 1);
+    fs::remove_file(&path).ok();
+}
@@ -0,0 +1,103 @@
+//! Items and stashes shared by the integration tests. Each test uses only some of them.
+#![allow(dead_code)]
+
+use helper::{AccountData, Item, MemorySource, StashData, StashSource};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Base types of a full chaos set with a two-handed weapon.
+pub const SLOTS: [&str; 9] = [
+    "Coral Amulet",
+    "Leather Belt",
+    "Astral Plate",
+    "Slink Boots",
+    "Vaal Gauntlets",
+    "Hubris Circlet",
+    "Two-Stone Ring",
+    "Two-Stone Ring",
+    "Judgement Staff",
+];
+
+/// A rare 1x1 item of ilvl 80 in the corner, as the API sends it. `fields` are added to it
+/// and win over these.
+pub fn item(fields: Value) -> Item {
+    let mut item = json!({"w": 1, "h": 1, "x": 0, "y": 0, "ilvl": 80, "frameType": 2});
+    if let (Some(item), Value::Object(fields)) = (item.as_object_mut(), fields) {
+        item.extend(fields);
+    }
+    serde_json::from_value(item).unwrap()
+}
+
+/// `sets` full chaos sets, one in each row. The amulet of each set is the item of ilvl
+/// 60 to 74 the chaos recipe needs.
+pub fn items_of_sets(sets: usize) -> Vec<Item> {
+    let mut items = Vec::new();
+    for set in 0..sets {
+        for (idx, base) in SLOTS.iter().enumerate() {
+            items.push(item(json!({
+                "id": format!("{}-{}", set, idx),
+                "x": idx,
+                "y": set,
+                "ilvl": if idx == 0 { 70 } else { 80 },
+                "baseType": base,
+            })));
+        }
+    }
+    items
+}
+
+/// A stash holding `sets` full chaos sets.
+pub fn stash_with_sets(sets: usize) -> MemorySource {
+    MemorySource::new(StashData {
+        items: items_of_sets(sets),
+        quad_layout: false,
+        tabs: Vec::new(),
+    })
+}
+
+/// A stash the test can change between fetches.
+#[derive(Clone)]
+pub struct SharedStash(Arc<Mutex<Vec<Item>>>);
+
+impl SharedStash {
+    pub fn new(items: Vec<Item>) -> Self {
+        Self(Arc::new(Mutex::new(items)))
+    }
+
+    pub fn take(&self, items: &[Item]) {
+        self.0.lock().unwrap().retain(|item| !items.contains(item));
+    }
+
+    pub fn put_back(&self, item: Item) {
+        self.0.lock().unwrap().push(item);
+    }
+}
+
+impl StashSource for SharedStash {
+    fn fetch(&mut self, _account: &AccountData, _tab_idx: usize) -> helper::Result<StashData> {
+        Ok(StashData {
+            items: self.0.lock().unwrap().clone(),
+            quad_layout: false,
+            tabs: Vec::new(),
+        })
+    }
+}
+
+/// A set in the stash of the league "Ritual", and nothing in any other league.
+pub struct LeagueSource;
+
+impl StashSource for LeagueSource {
+    fn fetch(&mut self, account: &AccountData, _tab_idx: usize) -> helper::Result<StashData> {
+        Ok(StashData {
+            items: match account.league.as_str() {
+                "Ritual" => items_of_sets(1),
+                _ => Vec::new(),
+            },
+            quad_layout: false,
+            tabs: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::{stash_with_sets, TIMEOUT};
+use helper::{
+    AccountData, ApiServer, HelperError, HelperSession, RecipeMode, StashData, StashEvent,
+    StashSource,
+};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A source whose cookie has expired.
+struct ExpiredSource;
+
+impl StashSource for ExpiredSource {
+    fn fetch(&mut self, _account: &AccountData, _tab_idx: usize) -> helper::Result<StashData> {
+        Err(HelperError::InvalidSession)
+    }
+}
+
+#[test]
+fn subscribers_see_fetches() {
+    let session = HelperSession::with_source(AccountData::default(), stash_with_sets(2));
+    let events = session.subscribe().unwrap();
+    match events.recv_timeout(TIMEOUT).unwrap() {
+        StashEvent::CountsChanged {
+            sets, generation, ..
+        } => assert_eq!((sets, generation), (0, 0)),
+        other => panic!("expected the current counts, got {:?}", other),
+    }
+
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+    match events.recv_timeout(TIMEOUT).unwrap() {
+        StashEvent::CountsChanged { counts, sets, .. } => {
+            assert_eq!(sets, 2);
+            assert_eq!(counts[&helper::ItemType::Ring], (0, 4));
+        }
+        other => panic!("expected new counts, got {:?}", other),
+    }
+    assert_eq!(
+        events.recv_timeout(TIMEOUT).unwrap(),
+        StashEvent::SetAvailable {
+            mode: RecipeMode::Chaos,
+            sets: 2
+        }
+    );
+
+    // 같은 창고를 다시 가져오거나 같은 세트를 다시 보면 바뀐 것이 없다.
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+    session
+        .acquire_chaos_list(false, RecipeMode::Chaos)
+        .unwrap();
+    assert!(events.recv_timeout(Duration::from_millis(100)).is_err());
+}
+
+#[test]
+fn auth_errors_are_sent() {
+    let session = HelperSession::with_source(AccountData::default(), ExpiredSource);
+    let events = session.subscribe().unwrap();
+    events.recv_timeout(TIMEOUT).unwrap();
+    assert!(session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .is_err());
+    match events.recv_timeout(TIMEOUT).unwrap() {
+        StashEvent::AuthError { suggestion, .. } => {
+            assert_eq!(suggestion, HelperError::InvalidSession.suggestion())
+        }
+        other => panic!("expected an auth error, got {:?}", other),
+    }
+}
+
+/// Reads one unmasked frame from the server.
+fn read_text_frame(stream: &mut impl Read) -> Value {
+    let mut head = [0; 2];
+    stream.read_exact(&mut head).unwrap();
+    assert_eq!(head[0], 0x81);
+    let len = match head[1] {
+        126 => {
+            let mut len = [0; 2];
+            stream.read_exact(&mut len).unwrap();
+            u16::from_be_bytes(len) as usize
+        }
+        len => len as usize,
+    };
+    let mut payload = vec![0; len];
+    stream.read_exact(&mut payload).unwrap();
+    serde_json::from_slice(&payload).unwrap()
+}
+
+/// Opens `/events` and checks the handshake.
+fn connect_events(server: &ApiServer) -> (TcpStream, BufReader<TcpStream>) {
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream.set_read_timeout(Some(TIMEOUT)).unwrap();
+    // RFC 6455에 나오는 예시 키
+    write!(
+        stream,
+        "GET /events HTTP/1.1\r\nHost: localhost:{}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        server.local_addr().port()
+    )
+    .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        if line.trim().is_empty() {
+            break;
+        }
+        headers.push(line.trim().to_owned());
+    }
+    assert!(headers[0].starts_with("HTTP/1.1 101"));
+    assert!(headers.contains(&"Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=".to_owned()));
+    (stream, reader)
+}
+
+/// Reads the close frame of the server and its status code.
+fn read_close_status(stream: &mut impl Read) -> u16 {
+    let mut head = [0; 4];
+    stream.read_exact(&mut head).unwrap();
+    assert_eq!(head[..2], [0x88, 2]);
+    u16::from_be_bytes([head[2], head[3]])
+}
+
+#[test]
+fn websocket_forwards_events() {
+    let session = Arc::new(HelperSession::with_source(
+        AccountData::default(),
+        stash_with_sets(1),
+    ));
+    let server = ApiServer::start(session.clone(), 0).unwrap();
+    let (mut stream, mut reader) = connect_events(&server);
+
+    assert_eq!(read_text_frame(&mut reader)["event"], "CountsChanged");
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+    let counts = read_text_frame(&mut reader);
+    assert_eq!(
+        (counts["event"].as_str(), counts["sets"].as_u64()),
+        (Some("CountsChanged"), Some(1))
+    );
+    assert_eq!(read_text_frame(&mut reader)["event"], "SetAvailable");
+
+    // 닫기 프레임에는 닫기 프레임으로 답한다.
+    stream.write_all(&[0x88, 0x80, 1, 2, 3, 4]).unwrap();
+    let mut head = [0; 2];
+    reader.read_exact(&mut head).unwrap();
+    assert_eq!(head[0], 0x88);
+}
+
+#[test]
+fn websocket_rejects_bad_frames() {
+    let session = Arc::new(HelperSession::with_source(
+        AccountData::default(),
+        stash_with_sets(1),
+    ));
+    let server = ApiServer::start(session, 0).unwrap();
+
+    // 가리지 않은 핑
+    let (mut stream, mut reader) = connect_events(&server);
+    read_text_frame(&mut reader);
+    stream.write_all(&[0x89, 0x00]).unwrap();
+    assert_eq!(read_close_status(&mut reader), 1002);
+
+    // 제어 프레임은 125바이트를 넘을 수 없다.
+    let (mut stream, mut reader) = connect_events(&server);
+    read_text_frame(&mut reader);
+    stream.write_all(&[0x89, 0x80 | 126, 0, 200]).unwrap();
+    assert_eq!(read_close_status(&mut reader), 1002);
+
+    // 길이만 보고 끊으므로 내용은 보내지 않아도 된다.
+    let (mut stream, mut reader) = connect_events(&server);
+    read_text_frame(&mut reader);
+    stream
+        .write_all(&[0x81, 0x80 | 127, 0, 0, 0, 1, 0, 0, 0, 0])
+        .unwrap();
+    assert_eq!(read_close_status(&mut reader), 1002);
+}
@@ -0,0 +1,278 @@
+mod common;
+
+use common::item;
+use helper::{
+    classify_items, inject_block, slot_counts, AccountData, ChaosRecipeSet, FilterFile, FilterRule,
+    HelperSession, ItemType, LootFilter, MemorySource, RecipeMode, BLOCK_BEGIN, BLOCK_END,
+};
+use serde_json::json;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const BASE: &str = "tests/fixtures/filter/base.filter";
+
+/// Two rings and a helmet, plus a helmet too low for any recipe.
+fn stash() -> ChaosRecipeSet {
+    classify_items(vec![
+        item(json!({"x": 0, "baseType": "Two-Stone Ring"})),
+        item(json!({"x": 1, "ilvl": 70, "baseType": "Two-Stone Ring"})),
+        item(json!({"x": 2, "baseType": "Hubris Circlet"})),
+        item(json!({"x": 3, "ilvl": 50, "baseType": "Hubris Circlet"})),
+    ])
+}
+
+fn filter(hide_saturated: bool) -> LootFilter {
+    LootFilter {
+        base: BASE.into(),
+        output: PathBuf::new(),
+        mode: RecipeMode::Chaos,
+        target_sets: 1,
+        hide_saturated,
+    }
+}
+
+/// The lines of the block for `slot`, from its `Show` or `Hide` to the empty line.
+fn slot_block(block: &str, slot: ItemType) -> Vec<&str> {
+    let header = format!(" # {} ", slot.as_ref());
+    block
+        .lines()
+        .skip_while(|line| !line.contains(&header))
+        .take_while(|line| !line.is_empty())
+        .collect()
+}
+
+#[test]
+fn counts_each_slot() {
+    let counts = slot_counts(&stash(), RecipeMode::Chaos, 1);
+    let count = |slot| counts.iter().find(|count| count.slot == slot).unwrap();
+    assert_eq!(
+        (count(ItemType::Ring).have, count(ItemType::Ring).wanted),
+        (2, 2)
+    );
+    assert_eq!(
+        (count(ItemType::Helmet).have, count(ItemType::Helmet).wanted),
+        (1, 1)
+    );
+    assert_eq!(count(ItemType::Weapon1HOrShield).wanted, 2);
+    assert_eq!(count(ItemType::Weapon2H).wanted, 1);
+    assert!(count(ItemType::Ring).is_saturated());
+    assert!(!count(ItemType::Body).is_saturated());
+    assert_eq!(counts.len(), 9);
+
+    // 리갈 레시피에는 ilvl 75 이상만 들어간다.
+    let counts = slot_counts(&stash(), RecipeMode::Regal, 4);
+    let ring = counts
+        .iter()
+        .find(|count| count.slot == ItemType::Ring)
+        .unwrap();
+    assert_eq!((ring.have, ring.wanted), (1, 8));
+}
+
+#[test]
+fn styles_slots_by_count() {
+    let block = filter(false).recipe_block(&stash());
+    assert!(block.starts_with(BLOCK_BEGIN));
+    assert!(block.ends_with(&format!("{}\n", BLOCK_END)));
+
+    let rings = slot_block(&block, ItemType::Ring);
+    assert_eq!(rings[0], "Show # Ring 2/2");
+    assert!(rings.contains(&"    Rarity Rare"));
+    assert!(rings.contains(&"    ItemLevel >= 60"));
+    assert!(rings.contains(&"    Class \"Rings\""));
+    assert!(rings.contains(&"    SetFontSize 18"));
+
+    let bodies = slot_block(&block, ItemType::Body);
+    assert_eq!(bodies[0], "Show # Body 0/1");
+    assert!(bodies.contains(&"    Class \"Body Armours\""));
+    assert!(bodies.contains(&"    PlayEffect Red"));
+
+    let weapons = slot_block(&block, ItemType::Weapon1HOrShield);
+    assert_eq!(weapons[0], "Show # Weapon1HOrShield 0/2");
+    assert!(weapons
+        .iter()
+        .any(|line| line.contains("\"Claws\"") && line.contains("\"Shields\"")));
+
+    let block = filter(true).recipe_block(&stash());
+    assert_eq!(slot_block(&block, ItemType::Ring)[0], "Hide # Ring 2/2");
+    assert_eq!(slot_block(&block, ItemType::Helmet)[0], "Hide # Helmet 1/1");
+    assert_eq!(slot_block(&block, ItemType::Body)[0], "Show # Body 0/1");
+}
+
+#[test]
+fn keeps_showing_required_item_levels() {
+    // 반지는 다 찼지만 ilvl 60–74 아이템이 하나도 없다.
+    let map = classify_items(vec![
+        item(json!({"x": 0, "baseType": "Two-Stone Ring"})),
+        item(json!({"x": 1, "baseType": "Two-Stone Ring"})),
+        item(json!({"x": 2, "ilvl": 90, "baseType": "Hubris Circlet"})),
+    ]);
+    let block = filter(true).recipe_block(&map);
+    let required: Vec<_> = block
+        .lines()
+        .skip_while(|line| !line.starts_with("Show # ItemLevel 60-74 0/1"))
+        .take_while(|line| !line.is_empty())
+        .collect();
+    assert!(required.contains(&"    ItemLevel >= 60"));
+    assert!(required.contains(&"    ItemLevel <= 74"));
+    assert!(required.contains(&"    Class \"Helmets\" \"Rings\""));
+    let position = |text: &str| block.find(text).unwrap();
+    assert!(position("Show # ItemLevel 60-74") < position("Hide # Ring 2/2"));
+
+    // 필요한 만큼 있으면 따로 보여주지 않는다.
+    let block = filter(true).recipe_block(&stash());
+    assert!(!block.contains("# ItemLevel"));
+}
+
+/// A dropped item, with what the filter conditions of the tests look at.
+struct Drop {
+    class: &'static str,
+    base: &'static str,
+    ilvl: usize,
+    links: usize,
+    influenced: bool,
+}
+
+fn rare_body(ilvl: usize, links: usize, influenced: bool) -> Drop {
+    Drop {
+        class: "Body Armours",
+        base: "Astral Plate",
+        ilvl,
+        links,
+        influenced,
+    }
+}
+
+fn compare(operator: Option<&str>, have: usize, value: &str) -> bool {
+    let value: usize = value.parse().unwrap();
+    match operator.unwrap_or("=") {
+        "<" => have < value,
+        "<=" => have <= value,
+        ">" => have > value,
+        ">=" => have >= value,
+        "=" | "==" => have == value,
+        other => panic!("unknown operator {}", other),
+    }
+}
+
+fn matches(rule: &FilterRule, drop: &Drop) -> bool {
+    let operator = rule.operator.as_deref();
+    let names = |name: &str| match operator {
+        Some("==") => rule.values.iter().any(|value| value == name),
+        _ => rule
+            .values
+            .iter()
+            .any(|value| name.contains(value.as_str())),
+    };
+    match rule.keyword.as_str() {
+        // 희귀 아이템만 떨어뜨린다.
+        "Rarity" => rule.values.iter().any(|value| match operator {
+            Some("<=") => ["Normal", "Magic", "Rare"].contains(&value.as_str()),
+            _ => value == "Rare",
+        }),
+        "ItemLevel" => compare(operator, drop.ilvl, &rule.values[0]),
+        "LinkedSockets" => compare(operator, drop.links, &rule.values[0]),
+        "Width" | "Height" => compare(operator, 2, &rule.values[0]),
+        "Class" => names(drop.class),
+        "BaseType" => names(drop.base),
+        "Identified" => rule.values[0] == "True",
+        "HasInfluence" => (rule.values[0] != "None") == drop.influenced,
+        "SocketGroup" => false,
+        other => panic!("unknown condition {}", other),
+    }
+}
+
+/// Comment of the first block of `filter` which shows or hides `drop`.
+fn first_match<'a>(filter: &'a FilterFile, drop: &Drop) -> Option<&'a str> {
+    filter
+        .blocks()
+        .into_iter()
+        .filter(|block| !block.actions.iter().any(|rule| rule.keyword == "Continue"))
+        .find(|block| block.conditions.iter().all(|rule| matches(rule, drop)))
+        .and_then(|block| block.comment)
+}
+
+#[test]
+fn leaves_overriding_items_to_the_base_filter() {
+    let base = fs::read_to_string("tests/fixtures/filter/neversink.filter").unwrap();
+    let map = classify_items(vec![item(json!({"ilvl": 70, "baseType": "Astral Plate"}))]);
+    let text = inject_block(&base, &filter(true).recipe_block(&map));
+    let filter = FilterFile::parse(&text);
+
+    assert_eq!(
+        first_match(&filter, &rare_body(70, 4, false)),
+        Some("Body 1/1")
+    );
+    assert_eq!(
+        first_match(&filter, &rare_body(70, 6, false)),
+        Some("$type->6l $tier->t1")
+    );
+    assert_eq!(
+        first_match(&filter, &rare_body(70, 4, true)),
+        Some("$type->rare $tier->remaining")
+    );
+    assert_eq!(
+        first_match(&filter, &rare_body(86, 4, false)),
+        Some("$type->rare $tier->remaining")
+    );
+}
+
+#[test]
+fn injecting_again_replaces_the_block() {
+    let base = fs::read_to_string(BASE).unwrap();
+    let first = inject_block(&base, &filter(false).recipe_block(&stash()));
+    assert!(first.starts_with(BLOCK_BEGIN));
+    assert!(first.ends_with(&base));
+
+    let block = filter(true).recipe_block(&ChaosRecipeSet::new());
+    let second = inject_block(&first, &block);
+    assert_eq!(second, inject_block(&base, &block));
+    assert_eq!(second.matches(BLOCK_BEGIN).count(), 1);
+    assert_eq!(inject_block(&second, &block), second);
+}
+
+#[test]
+fn keeps_crlf_line_endings() {
+    let base = fs::read_to_string(BASE).unwrap().replace('\n', "\r\n");
+    let filter = inject_block(&base, &filter(false).recipe_block(&stash()));
+    assert_eq!(filter.matches('\n').count(), filter.matches("\r\n").count());
+    assert!(filter.ends_with(&base));
+}
+
+#[test]
+fn writes_the_filter_when_counts_change() {
+    let output = std::env::temp_dir().join(format!("chaos_helper_{}.filter", std::process::id()));
+    fs::remove_file(&output).ok();
+    let filter = LootFilter {
+        output: output.clone(),
+        ..filter(true)
+    };
+    let source = MemorySource::from_json(
+        r#"{"items": [
+            {"w": 1, "h": 1, "x": 0, "y": 0, "ilvl": 80, "frameType": 2, "baseType": "Hubris Circlet"}
+        ]}"#,
+    )
+    .unwrap();
+    let session = HelperSession::with_source(AccountData::default(), source);
+    filter.keep_updated(&session).unwrap();
+    // 가져오기 전에는 아무것도 쓰지 않는다.
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(!output.exists());
+
+    session
+        .refresh_stash_status(RecipeMode::Chaos, Duration::from_secs(5))
+        .unwrap();
+    let base = fs::read_to_string(BASE).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let written = loop {
+        // 쓰는 도중에 읽었을 수도 있다.
+        match fs::read_to_string(&output) {
+            Ok(written) if written.ends_with(&base) => break written,
+            _ => {}
+        }
+        assert!(Instant::now() < deadline, "the filter was not written");
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    assert!(written.contains("Hide # Helmet 1/1"));
+    fs::remove_file(&output).ok();
+}
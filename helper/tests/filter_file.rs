@@ -0,0 +1,154 @@
+use helper::{
+    inject_block, FilterFile, FilterRule, LineEnding, LineKind, Visibility, BLOCK_BEGIN, BLOCK_END,
+};
+use std::fs;
+
+const FIXTURES: [&str; 4] = [
+    "tests/fixtures/filter/base.filter",
+    "tests/fixtures/filter/neversink.filter",
+    "tests/fixtures/filter/filterblade.filter",
+    "tests/fixtures/filter/injected.filter",
+];
+
+fn read(name: &str) -> (String, FilterFile) {
+    let text = fs::read_to_string(format!("tests/fixtures/filter/{}.filter", name)).unwrap();
+    let filter = FilterFile::parse(&text);
+    (text, filter)
+}
+
+fn values(rule: &FilterRule) -> Vec<&str> {
+    rule.values.iter().map(String::as_str).collect()
+}
+
+#[test]
+fn writes_fixtures_back_unchanged() {
+    for path in FIXTURES.iter() {
+        let text = fs::read_to_string(path).unwrap();
+        assert_eq!(
+            FilterFile::read(path).unwrap().to_string(),
+            text,
+            "{}",
+            path
+        );
+    }
+}
+
+#[test]
+fn reads_neversink_blocks() {
+    let (_, filter) = read("neversink");
+    assert_eq!(filter.line_ending(), LineEnding::CrLf);
+    let blocks = filter.blocks();
+    assert_eq!(blocks.len(), 9);
+
+    let shaper = &blocks[2];
+    assert_eq!(shaper.visibility, Visibility::Show);
+    assert_eq!(shaper.comment, Some("$type->crafting $tier->shaper"));
+    assert_eq!(shaper.conditions.len(), 4);
+    assert_eq!(shaper.actions.len(), 6);
+    let base_type = shaper.conditions[3];
+    assert_eq!(
+        (base_type.keyword.as_str(), base_type.operator.as_deref()),
+        ("BaseType", Some("=="))
+    );
+    assert_eq!(
+        values(base_type),
+        vec!["Hubris Circlet", "Sorcerer Boots", "Vaal Regalia"]
+    );
+    assert_eq!(shaper.actions[3].comment.as_deref(), Some("Shaper purple"));
+
+    // 주석 처리된 블록은 블록이 아니다.
+    assert!(filter.lines()[shaper.lines.end + 1..]
+        .iter()
+        .take(4)
+        .all(|line| matches!(line.kind(), LineKind::Comment(_))));
+
+    let weapons = &blocks[3];
+    assert_eq!(values(weapons.conditions[4]), vec!["Bows", "Two Hand"]);
+    assert_eq!(weapons.actions.last().unwrap().keyword, "Continue");
+
+    // 들여쓴 주석 다음 줄도 같은 블록이다.
+    let jewellery = &blocks[4];
+    let ilvl = jewellery.conditions[0];
+    assert_eq!(
+        (ilvl.operator.as_deref(), values(ilvl)),
+        (Some(">="), vec!["60"])
+    );
+    assert_eq!(jewellery.actions.len(), 2);
+    assert_eq!(jewellery.lines.len(), 7);
+
+    assert_eq!(blocks[5].visibility, Visibility::Minimal);
+    assert_eq!(blocks[8].visibility, Visibility::Hide);
+    assert_eq!(values(blocks[7].actions[0]), vec!["sounds\\chaos.mp3"]);
+}
+
+#[test]
+fn reads_filterblade_blocks() {
+    let (text, filter) = read("filterblade");
+    assert!(text.starts_with('\u{feff}'));
+    assert_eq!(filter.line_ending(), LineEnding::Lf);
+    assert_eq!(filter.lines().last().unwrap().ending(), LineEnding::None);
+    assert!(matches!(filter.lines()[0].kind(), LineKind::Comment(_)));
+
+    let blocks = filter.blocks();
+    assert_eq!(blocks.len(), 4);
+    assert_eq!(blocks[0].comment, None);
+    let mods = blocks[0].conditions[0];
+    assert_eq!(
+        (mods.operator.as_deref(), values(mods)),
+        (Some(">="), vec!["1", "of Haast", "of Tzteosh"])
+    );
+    assert_eq!(blocks[0].actions[0].comment.as_deref(), Some("big"));
+
+    assert_eq!(blocks[1].comment, Some("\"quoted # hash\" in the comment"));
+    assert_eq!(values(blocks[1].actions[0]), vec!["", "300"]);
+    assert!(blocks[1].actions[1].values.is_empty());
+
+    let operators: Vec<_> = blocks[2]
+        .conditions
+        .iter()
+        .map(|rule| rule.operator.as_deref())
+        .collect();
+    assert_eq!(
+        operators,
+        vec![Some("<"), Some(">"), Some(">="), Some("!="), None]
+    );
+    assert_eq!(blocks[3].lines.end, filter.lines().len());
+}
+
+#[test]
+fn replaces_the_helper_block_where_it_is() {
+    let (text, mut filter) = read("injected");
+    let range = filter.helper_block().unwrap();
+    assert_eq!(range, 3..12);
+    assert_eq!(filter.blocks()[0].comment, Some("Ring 8/8"));
+
+    let block = format!(
+        "{}\nShow # Ring 1/8\n    Class \"Rings\"\n{}\n",
+        BLOCK_BEGIN, BLOCK_END
+    );
+    filter.set_helper_block(&block);
+    let lines: Vec<_> = text.split_inclusive('\n').collect();
+    assert_eq!(
+        filter.to_string(),
+        format!("{}{}{}", lines[..3].concat(), block, lines[12..].concat())
+    );
+    assert_eq!(filter.helper_block(), Some(3..7));
+    assert_eq!(filter.blocks().len(), 3);
+}
+
+#[test]
+fn puts_a_new_block_after_the_bom() {
+    let (text, _) = read("filterblade");
+    let block = format!("{}\n{}\n", BLOCK_BEGIN, BLOCK_END);
+    let filter = inject_block(&text, &block);
+    assert_eq!(
+        filter,
+        format!("\u{feff}{}\n{}", block, text.trim_start_matches('\u{feff}'))
+    );
+    assert_eq!(inject_block(&filter, &block), filter);
+
+    let (text, _) = read("neversink");
+    let filter = inject_block(&text, &block);
+    assert!(filter.starts_with(&format!("{}\r\n{}\r\n\r\n", BLOCK_BEGIN, BLOCK_END)));
+    assert!(filter.ends_with(&text));
+}
@@ -0,0 +1,80 @@
+mod common;
+
+use common::item;
+use helper::{classify_items, recipe_queue, summarize_influences, Influence, Item, RecipeMode};
+use serde_json::{json, Value};
+
+const BASES: [&str; 8] = [
+    "Coral Amulet",
+    "Leather Belt",
+    "Astral Plate",
+    "Slink Boots",
+    "Vaal Gauntlets",
+    "Hubris Circlet",
+    "Two-Stone Ring",
+    "Judgement Staff",
+];
+
+/// One full set, every item with the influence `fields`, plus a second ring.
+fn set(first_x: usize, fields: Value) -> Vec<Item> {
+    BASES
+        .iter()
+        .chain(std::iter::once(&"Two-Stone Ring"))
+        .enumerate()
+        .map(|(x, base)| {
+            let mut fields = fields.clone();
+            fields["x"] = json!(first_x + x);
+            fields["baseType"] = json!(base);
+            item(fields)
+        })
+        .collect()
+}
+
+#[test]
+fn reads_influences_and_legacy_flags() {
+    let influenced = item(json!({
+        "baseType": "Two-Stone Ring",
+        "influences": {"crusader": true, "hunter": false},
+    }));
+    assert_eq!(influenced.influences(), vec![Influence::Crusader]);
+
+    let legacy = item(json!({
+        "x": 1,
+        "baseType": "Two-Stone Ring",
+        "shaper": true,
+        "elder": true,
+    }));
+    assert_eq!(
+        legacy.influences(),
+        vec![Influence::Shaper, Influence::Elder]
+    );
+}
+
+#[test]
+fn influenced_sets_share_one_influence() {
+    let mut items = set(0, json!({"influences": {"warlord": true}}));
+    items.extend(set(100, json!({"elder": true})));
+    // 영향력이 섞이면 세트가 되지 않는다.
+    items[0] = item(json!({
+        "x": 200,
+        "baseType": "Coral Amulet",
+        "influences": {"hunter": true},
+    }));
+    let map = classify_items(items);
+
+    let sets = recipe_queue(&map, RecipeMode::Influenced);
+    assert_eq!(sets.len(), 1);
+    assert!(sets[0]
+        .iter()
+        .all(|item| item.has_influence(Influence::Elder)));
+
+    let summary = summarize_influences(&map);
+    let of = |influence| summary.iter().find(|s| s.influence == influence).unwrap();
+    assert_eq!(
+        (of(Influence::Elder).sets, of(Influence::Warlord).sets),
+        (1, 0)
+    );
+    assert_eq!(of(Influence::Warlord).items.values().sum::<usize>(), 8);
+    assert_eq!(of(Influence::Hunter).items.values().sum::<usize>(), 1);
+    assert!(summary.iter().all(|s| s.influence != Influence::Shaper));
+}
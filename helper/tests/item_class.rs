@@ -0,0 +1,121 @@
+mod common;
+
+use common::item;
+use helper::{unclassified_items, Item, ItemType};
+use serde_json::json;
+
+fn by_base(base: &str) -> ItemType {
+    item(json!({"baseType": base})).item_type()
+}
+
+#[test]
+fn weapons_by_base_type() {
+    let one_handed = [
+        "Imperial Claw",
+        "Gemini Claw",
+        "Ambusher",
+        "Sai",
+        "Platinum Kris",
+        "Fiend Dagger",
+        "Convoking Wand",
+        "Void Sceptre",
+        "Runic Hatchet",
+        "Jewelled Foil",
+        "Titanium Spirit Shield",
+    ];
+    for base in one_handed.iter() {
+        assert_eq!(by_base(base), ItemType::Weapon1HOrShield, "{}", base);
+    }
+    let two_handed = [
+        "Eclipse Staff",
+        "Judgement Staff",
+        "Maelström Staff",
+        "Iron Staff",
+        "Thicket Bow",
+        "Coronal Maul",
+        "Two-Handed Sword",
+    ];
+    for base in two_handed.iter() {
+        assert_eq!(by_base(base), ItemType::Weapon2H, "{}", base);
+    }
+}
+
+#[test]
+fn armour_and_accessories_by_base_type() {
+    assert_eq!(by_base("Spike-Point Arrow Quiver"), ItemType::Useless);
+    assert_eq!(by_base("Leather Belt"), ItemType::Belt);
+    assert_eq!(by_base("Stygian Vise"), ItemType::Belt);
+    assert_eq!(by_base("Full Ringmail"), ItemType::Body);
+    assert_eq!(by_base("Full Dragonscale"), ItemType::Body);
+    assert_eq!(by_base("Unset Ring"), ItemType::Ring);
+    assert_eq!(by_base("Onyx Amulet"), ItemType::Amulet);
+    assert_eq!(by_base("Sorcerer Boots"), ItemType::Boots);
+    assert_eq!(by_base("Fingerless Silk Gloves"), ItemType::Gloves);
+    assert_eq!(by_base("Lion Pelt"), ItemType::Helmet);
+}
+
+#[test]
+fn bases_without_a_common_last_word() {
+    assert_eq!(by_base("Golden Mantle"), ItemType::Body);
+    assert_eq!(by_base("Sacrificial Garb"), ItemType::Body);
+    assert_eq!(by_base("Bone Helmet"), ItemType::Helmet);
+    assert_eq!(by_base("Two-Toned Boots"), ItemType::Boots);
+    assert_eq!(by_base("Synthesised Iron Ring"), ItemType::Ring);
+}
+
+#[test]
+fn category_wins_over_base_type() {
+    let extended = item(json!({
+        "typeLine": "Something Else",
+        "extended": {"category": "weapons", "subcategories": ["runedagger"]},
+    }));
+    assert_eq!(extended.item_type(), ItemType::Weapon1HOrShield);
+    let warstaff = item(json!({
+        "typeLine": "Something Else",
+        "extended": {"category": "weapons", "subcategories": ["warstaff"]},
+    }));
+    assert_eq!(warstaff.item_type(), ItemType::Weapon2H);
+    let quiver = item(json!({"typeLine": "Light Quiver", "category": {"armour": ["quiver"]}}));
+    assert_eq!(quiver.item_type(), ItemType::Useless);
+    let jewel = item(json!({"typeLine": "Cobalt Jewel", "extended": {"category": "jewels"}}));
+    assert_eq!(jewel.item_type(), ItemType::Useless);
+
+    // 하위 분류가 없으면 기본 아이템 표를 쓴다.
+    let claw = item(json!({"baseType": "Vaal Claw", "extended": {"category": "weapons"}}));
+    assert_eq!(claw.item_type(), ItemType::Weapon1HOrShield);
+}
+
+#[test]
+fn unknown_items_are_reported() {
+    let items = vec![
+        item(json!({"baseType": "Coral Amulet"})),
+        item(json!({"baseType": "Mystery Trinket Of Doom"})),
+        item(json!({"typeLine": "Nothing Known"})),
+    ];
+    let unclassified = unclassified_items(&items);
+    let bases: Vec<&str> = unclassified
+        .iter()
+        .map(|item| item.base_type.as_str())
+        .collect();
+    assert_eq!(bases, vec!["Mystery Trinket Of Doom", "Nothing Known"]);
+}
+
+#[test]
+fn written_items_read_back_the_same() {
+    // 기본 아이템 표에 없는 이름이어도 분류가 남는다.
+    let mut warstaff = item(json!({
+        "id": "abc",
+        "typeLine": "Something Else",
+        "identified": false,
+        "influences": {"shaper": true, "hunter": true},
+        "extended": {"category": "weapons", "subcategories": ["warstaff"]},
+    }));
+    warstaff.tab_idx = 3;
+    warstaff.quad_tab = true;
+    let items = vec![warstaff, item(json!({"baseType": "Coral Amulet"}))];
+
+    let json = serde_json::to_string(&items).unwrap();
+    let read: Vec<Item> = serde_json::from_str(&json).unwrap();
+    assert_eq!(read, items);
+    assert_eq!(read[0].item_type(), ItemType::Weapon2H);
+}
@@ -0,0 +1,185 @@
+mod common;
+
+use common::{items_of_sets, LeagueSource, SharedStash, SLOTS, TIMEOUT};
+use helper::{
+    earnings_by_league, earnings_by_session, AccountData, Currency, Earnings, HelperSession,
+    Ledger, RecipeMode, ResponseFromNetwork, Sale,
+};
+use std::time::{Duration, Instant};
+
+#[test]
+fn reads_sales_and_sums_them() {
+    let sales = Ledger::new("tests/fixtures/ledger/sales.jsonl")
+        .sales()
+        .unwrap();
+    assert_eq!(sales.len(), 4);
+    assert_eq!(
+        sales[2].items[0].influences,
+        vec![helper::Influence::Shaper]
+    );
+
+    let total = Earnings::of(&sales);
+    assert_eq!((total.sets, total.unidentified_sets), (4, 2));
+    assert_eq!(total.orbs[&Currency::Chaos], 4);
+    assert_eq!(total.orbs[&Currency::Regal], 2);
+    assert!((total.hours - 3.25).abs() < 1e-9);
+
+    let sessions = earnings_by_session(&sales);
+    assert_eq!(
+        sessions.keys().copied().collect::<Vec<_>>(),
+        vec![1000, 10000, 20000]
+    );
+    assert!((sessions[&1000].per_hour(Currency::Chaos) - 3.0).abs() < 1e-9);
+    assert!((sessions[&10000].per_hour(Currency::Regal) - 8.0).abs() < 1e-9);
+    assert_eq!(sessions[&10000].per_hour(Currency::Chaos), 0.0);
+
+    let leagues = earnings_by_league(&sales);
+    assert_eq!(leagues.len(), 2);
+    assert!((leagues["Ritual"].per_hour(Currency::Chaos) - 2.4).abs() < 1e-9);
+    assert!((leagues["Standard"].per_hour(Currency::Chaos) - 0.5).abs() < 1e-9);
+}
+
+#[test]
+fn damaged_lines_are_skipped() {
+    let (sales, skipped) = Ledger::new("tests/fixtures/ledger/damaged.jsonl")
+        .read()
+        .unwrap();
+    assert_eq!((sales.len(), skipped), (4, 1));
+
+    // 쓰다 만 줄 뒤에는 새 줄부터 쓴다.
+    let path =
+        std::env::temp_dir().join(format!("chaos_helper_damaged_{}.jsonl", std::process::id()));
+    std::fs::write(&path, r#"{"time":10900,"session":"#).unwrap();
+    let ledger = Ledger::new(&path);
+    ledger.append(&sales[0]).unwrap();
+    let read = ledger.read().unwrap();
+    std::fs::remove_file(&path).ok();
+    assert_eq!(read, (vec![sales[0].clone()], 1));
+}
+
+#[test]
+fn a_missing_ledger_is_empty() {
+    let ledger = Ledger::new("tests/fixtures/ledger/nothing.jsonl");
+    assert_eq!(ledger.sales().unwrap(), Vec::new());
+    assert_eq!(Earnings::of(&[]).per_hour(Currency::Chaos), 0.0);
+}
+
+/// Picks up the next set of `session` and fetches the stash without it.
+fn sell_next_set(session: &HelperSession, stash: &SharedStash) {
+    match session
+        .acquire_chaos_list(false, RecipeMode::Chaos)
+        .unwrap()
+    {
+        ResponseFromNetwork::ChaosRecipe { items, .. } => {
+            assert_eq!(items.len(), SLOTS.len());
+            stash.take(&items);
+        }
+        other => panic!("expected a set, got {:?}", other),
+    }
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+}
+
+/// Sales of `ledger` once there are `count` of them, as the recording thread writes them.
+fn wait_for_sales(ledger: &Ledger, count: usize) -> Vec<Sale> {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let sales = ledger.sales().unwrap();
+        if sales.len() >= count || Instant::now() > deadline {
+            return sales;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn consumed_sets_are_recorded() {
+    let mut items = items_of_sets(1);
+    for item in items.iter_mut() {
+        item.identified = false;
+    }
+    let stash = SharedStash::new(items.clone());
+    let account = AccountData {
+        league: "Ritual".to_owned(),
+        ..Default::default()
+    };
+    let session = HelperSession::with_source(account, stash.clone());
+    let path =
+        std::env::temp_dir().join(format!("chaos_helper_sales_{}.jsonl", std::process::id()));
+    std::fs::remove_file(&path).ok();
+    let ledger = Ledger::new(&path);
+    ledger.record(&session).unwrap();
+
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+    sell_next_set(&session, &stash);
+
+    let sales = wait_for_sales(&ledger, 1);
+    std::fs::remove_file(&path).ok();
+    assert_eq!(sales.len(), 1);
+    let sale = &sales[0];
+    assert_eq!(sale.league, "Ritual");
+    assert_eq!(sale.recipe, RecipeMode::Chaos);
+    assert!(!sale.identified);
+    assert_eq!((sale.currency, sale.amount), (Currency::Chaos, 2));
+    assert_eq!(sale.items.len(), SLOTS.len());
+    assert!(sale.time >= sale.session);
+}
+
+#[test]
+fn unwritten_sales_are_written_with_the_next_one() {
+    let stash = SharedStash::new(items_of_sets(2));
+    let session = HelperSession::with_source(AccountData::default(), stash.clone());
+    // 폴더가 없으니 처음에는 쓸 수 없다.
+    let dir = std::env::temp_dir().join(format!("chaos_helper_ledger_{}", std::process::id()));
+    std::fs::remove_dir_all(&dir).ok();
+    let ledger = Ledger::new(dir.join("sales.jsonl"));
+    ledger.record(&session).unwrap();
+
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+    sell_next_set(&session, &stash);
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(ledger.sales().unwrap().is_empty());
+
+    std::fs::create_dir(&dir).unwrap();
+    sell_next_set(&session, &stash);
+    let sales = wait_for_sales(&ledger, 2);
+    std::fs::remove_dir_all(&dir).ok();
+    assert_eq!(sales.len(), 2);
+    assert!(sales[0].time <= sales[1].time);
+}
+
+#[test]
+fn switching_leagues_is_not_a_sale() {
+    let account = AccountData {
+        league: "Ritual".to_owned(),
+        ..Default::default()
+    };
+    let session = HelperSession::with_source(account.clone(), LeagueSource);
+    let path =
+        std::env::temp_dir().join(format!("chaos_helper_leagues_{}.jsonl", std::process::id()));
+    std::fs::remove_file(&path).ok();
+    let ledger = Ledger::new(&path);
+    ledger.record(&session).unwrap();
+
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+    session
+        .acquire_chaos_list(false, RecipeMode::Chaos)
+        .unwrap();
+    session.set_account(AccountData {
+        league: "Standard".to_owned(),
+        ..account
+    });
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(100));
+    assert!(ledger.sales().unwrap().is_empty());
+    std::fs::remove_file(&path).ok();
+}
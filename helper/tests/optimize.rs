@@ -0,0 +1,137 @@
+mod common;
+
+use common::{item, items_of_sets};
+use helper::{
+    classify_items, optimal_chaos_sets, optimal_sets, ChaosListGenerator, Item, ItemType,
+    RecipeMode, SlotItems, SlotRule,
+};
+use proptest::prelude::*;
+use serde_json::json;
+
+const BASES: [&str; 10] = [
+    "Coral Amulet",
+    "Leather Belt",
+    "Astral Plate",
+    "Slink Boots",
+    "Vaal Gauntlets",
+    "Hubris Circlet",
+    "Two-Stone Ring",
+    "Judgement Staff",
+    "Imperial Claw",
+    "Titanium Spirit Shield",
+];
+
+/// Items of random types and levels. `x` tells them apart.
+fn items() -> impl Strategy<Value = Vec<Item>> {
+    prop::collection::vec((0..BASES.len(), 60..86usize), 0..60).prop_map(|specs| {
+        specs
+            .into_iter()
+            .enumerate()
+            .map(|(x, (base, ilvl))| item(json!({"x": x, "ilvl": ilvl, "baseType": BASES[base]})))
+            .collect()
+    })
+}
+
+fn is_valid_chaos_set(set: &[Item]) -> bool {
+    let count = |i_type| set.iter().filter(|item| item.item_type() == i_type).count();
+    let weapons_ok = matches!(
+        (count(ItemType::Weapon2H), count(ItemType::Weapon1HOrShield)),
+        (1, 0) | (0, 2)
+    );
+    weapons_ok
+        && [
+            ItemType::Amulet,
+            ItemType::Belt,
+            ItemType::Body,
+            ItemType::Boots,
+            ItemType::Gloves,
+            ItemType::Helmet,
+        ]
+        .iter()
+        .all(|i_type| count(*i_type) == 1)
+        && count(ItemType::Ring) == 2
+        && set.iter().any(|item| item.ilvl() < 75)
+}
+
+proptest! {
+    #[test]
+    fn never_fewer_sets_than_greedy(items in items()) {
+        let map = classify_items(items);
+        let (sets, _) = optimal_chaos_sets(&map);
+        prop_assert!(sets.len() >= ChaosListGenerator::new(&map).count());
+    }
+
+    #[test]
+    fn sets_are_valid_and_use_each_item_once(items in items()) {
+        let map = classify_items(items);
+        let (sets, remaining) = optimal_chaos_sets(&map);
+        for set in sets.iter() {
+            prop_assert!(is_valid_chaos_set(set), "invalid set {:?}", set);
+        }
+
+        let mut used: Vec<usize> = sets.iter().flatten().map(|item| item.x).collect();
+        used.extend(
+            remaining
+                .values()
+                .flat_map(|(chaos, regal)| chaos.iter().chain(regal.iter()))
+                .map(|item| item.x),
+        );
+        used.sort_unstable();
+        let mut all: Vec<usize> = map
+            .values()
+            .flat_map(|(chaos, regal)| chaos.iter().chain(regal.iter()))
+            .map(|item| item.x)
+            .collect();
+        all.sort_unstable();
+        prop_assert_eq!(used, all);
+    }
+}
+
+#[test]
+fn prefers_two_handed_weapons_and_few_chaos_items() {
+    let mut specs = vec![(7, 80), (7, 80), (8, 70), (8, 80), (9, 80), (9, 80)];
+    for base in 0..6 {
+        specs.extend(vec![(base, 70), (base, 80), (base, 80)]);
+    }
+    specs.extend(vec![(6, 80); 4]);
+    let items = specs
+        .into_iter()
+        .enumerate()
+        .map(|(x, (base, ilvl))| item(json!({"x": x, "ilvl": ilvl, "baseType": BASES[base]})))
+        .collect();
+    let map = classify_items(items);
+    let (sets, _) = optimal_chaos_sets(&map);
+    assert_eq!(sets.len(), 2);
+    for set in sets.iter() {
+        assert!(set
+            .iter()
+            .any(|item| item.item_type() == ItemType::Weapon2H));
+        assert_eq!(set.iter().filter(|item| item.ilvl() < 75).count(), 1);
+    }
+}
+
+#[test]
+fn builds_the_slots_of_the_rule() {
+    // 반지가 없는 레시피에서는 반지가 남는다.
+    let mut rule = RecipeMode::Chaos.rule().clone();
+    rule.slots
+        .retain(|slot| !matches!(slot, SlotRule::Items(items) if items.types == [ItemType::Ring]));
+    let (sets, remaining) = optimal_sets(&classify_items(items_of_sets(2)), &rule).unwrap();
+    assert_eq!(sets.len(), 2);
+    assert!(sets.iter().all(|set| set.len() == 7));
+    assert!(sets
+        .iter()
+        .flatten()
+        .all(|item| item.item_type() != ItemType::Ring));
+    let (chaos, regal) = &remaining[&ItemType::Ring];
+    assert_eq!(chaos.len() + regal.len(), 4);
+
+    // 알 수 없는 슬롯은 RuleGenerator에게 맡긴다.
+    let mut rule = RecipeMode::Chaos.rule().clone();
+    rule.slots.push(SlotRule::Items(SlotItems {
+        types: vec![ItemType::Boots, ItemType::Gloves],
+        count: 1,
+    }));
+    assert!(optimal_sets(&classify_items(items_of_sets(2)), &rule).is_none());
+    assert!(optimal_sets(&classify_items(items_of_sets(2)), RecipeMode::Regal.rule()).is_none());
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use common::{items_of_sets, SharedStash, SLOTS, TIMEOUT};
+use futures::executor::block_on;
+use helper::{
+    removed_items, AccountData, HelperSession, Item, RecipeMode, ResponseFromNetwork, StashEvent,
+};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+fn session_with_sets(sets: usize) -> (HelperSession, SharedStash, Receiver<StashEvent>) {
+    let stash = SharedStash::new(items_of_sets(sets));
+    let session = HelperSession::with_source(AccountData::default(), stash.clone());
+    let events = session.subscribe().unwrap();
+    session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap();
+    // 처음 개수와 첫 가져오기의 이벤트
+    while events.recv_timeout(Duration::from_millis(100)).is_ok() {}
+    (session, stash, events)
+}
+
+/// (items, picked items) of the set to pick up now.
+fn next_set(session: &HelperSession) -> (Vec<Item>, Vec<Item>) {
+    match block_on(session.next_set(RecipeMode::Chaos)).unwrap() {
+        ResponseFromNetwork::ChaosRecipe { items, picked, .. } => (items, picked),
+        other => panic!("expected a set, got {:?}", other),
+    }
+}
+
+fn refresh(session: &HelperSession) -> usize {
+    match session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap()
+    {
+        ResponseFromNetwork::StashStatus { sets, .. } => sets,
+        other => panic!("expected the stash status, got {:?}", other),
+    }
+}
+
+#[test]
+fn showing_the_set_again_stays_on_it() {
+    let (session, _stash, _events) = session_with_sets(2);
+    let (first, picked) = next_set(&session);
+    assert_eq!((first.len(), picked.len()), (SLOTS.len(), 0));
+    assert_eq!(next_set(&session).0, first);
+    assert_eq!(refresh(&session), 2);
+    assert_eq!(next_set(&session).0, first);
+
+    let sets = block_on(session.sets(RecipeMode::Chaos)).unwrap();
+    assert_eq!(sets.len(), 2);
+    match &sets[0] {
+        ResponseFromNetwork::ChaosRecipe { items, .. } => assert_eq!(*items, first),
+        other => panic!("expected a set, got {:?}", other),
+    }
+}
+
+#[test]
+fn partly_picked_sets_show_the_rest() {
+    let (session, stash, events) = session_with_sets(2);
+    let (set, _) = next_set(&session);
+    stash.take(&set[..3]);
+    assert_eq!(refresh(&session), 2);
+    match events.recv_timeout(TIMEOUT).unwrap() {
+        StashEvent::CountsChanged { sets, .. } => assert_eq!(sets, 2),
+        other => panic!("expected new counts, got {:?}", other),
+    }
+    match events.recv_timeout(TIMEOUT).unwrap() {
+        StashEvent::SetPartlyPicked { picked, left, .. } => {
+            assert_eq!((picked, left), (set[..3].to_vec(), set[3..].to_vec()))
+        }
+        other => panic!("expected a partly picked set, got {:?}", other),
+    }
+    assert_eq!(next_set(&session), (set[3..].to_vec(), set[..3].to_vec()));
+
+    // 다시 넣어 둔 아이템은 다시 보여준다.
+    stash.put_back(set[0].clone());
+    refresh(&session);
+    let (items, picked) = next_set(&session);
+    assert_eq!((items.len(), picked), (SLOTS.len() - 2, set[1..3].to_vec()));
+}
+
+#[test]
+fn consumed_sets_move_on() {
+    let (session, stash, events) = session_with_sets(2);
+    let (first, _) = next_set(&session);
+    stash.take(&first);
+    assert_eq!(refresh(&session), 1);
+    events.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(
+        events.recv_timeout(TIMEOUT).unwrap(),
+        StashEvent::SetConsumed {
+            mode: RecipeMode::Chaos,
+            items: first.clone(),
+            league: String::new(),
+            remaining: 1
+        }
+    );
+
+    let (second, _) = next_set(&session);
+    assert_eq!(second.len(), SLOTS.len());
+    assert!(second.iter().all(|item| !first.contains(item)));
+}
+
+#[test]
+fn moved_items_count_as_removed() {
+    let set = items_of_sets(1);
+    assert_eq!(removed_items(&set, &set), Vec::new());
+
+    let mut moved = set.clone();
+    moved[0].x = 20;
+    assert_eq!(removed_items(&set, &moved), vec![set[0].clone()]);
+
+    // 같은 자리에 있어도 id가 다르면 다른 아이템이다.
+    let mut other: Vec<Item> = serde_json::from_str(
+        r#"[{"id": "other", "w": 1, "h": 1, "x": 1, "y": 0, "ilvl": 80, "frameType": 2,
+            "baseType": "Leather Belt"}]"#,
+    )
+    .unwrap();
+    assert_eq!(removed_items(&set[1..2], &other), set[1..2].to_vec());
+    // id가 없으면 베이스 타입으로 구별한다.
+    other = serde_json::from_str(
+        r#"[{"w": 1, "h": 1, "x": 1, "y": 0, "ilvl": 80, "frameType": 2,
+            "baseType": "Leather Belt"}]"#,
+    )
+    .unwrap();
+    assert_eq!(removed_items(&set[1..2], &other), Vec::new());
+}
@@ -0,0 +1,119 @@
+use helper::{AccountData, HelperError, LiveSource, RateLimiter, Realm, StashSource};
+use reqwest::header::{HeaderMap, HeaderValue};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+    let mut map = HeaderMap::new();
+    for (name, value) in pairs {
+        map.insert(*name, HeaderValue::from_static(value));
+    }
+    map
+}
+
+#[test]
+fn limiter_follows_headers() {
+    let mut limiter = RateLimiter::new(Duration::from_secs(0));
+    let now = Instant::now();
+    assert_eq!(limiter.wait_time(now), None);
+
+    limiter.update(
+        &headers(&[
+            ("X-Rate-Limit-Rules", "Account"),
+            ("X-Rate-Limit-Account", "3:10:60,30:300:600"),
+            ("X-Rate-Limit-Account-State", "1:10:0,1:300:0"),
+        ]),
+        now,
+    );
+    assert_eq!(limiter.rules().len(), 2);
+    assert_eq!(limiter.rules()[0].max_hits, 3);
+    assert_eq!(limiter.rules()[1].period, Duration::from_secs(300));
+    assert_eq!(limiter.wait_time(now), None);
+
+    limiter.update(
+        &headers(&[
+            ("X-Rate-Limit-Rules", "Account"),
+            ("X-Rate-Limit-Account", "3:10:60"),
+            ("X-Rate-Limit-Account-State", "3:10:0"),
+        ]),
+        now,
+    );
+    assert_eq!(limiter.wait_time(now), Some(Duration::from_secs(10)));
+    assert!(limiter.wait_time(now + Duration::from_secs(10)).is_none());
+
+    limiter.update(&headers(&[("Retry-After", "120")]), now);
+    assert_eq!(
+        limiter.wait_time(now + Duration::from_secs(20)),
+        Some(Duration::from_secs(100))
+    );
+    match limiter.acquire() {
+        Err(HelperError::RateLimited { retry_after }) => assert!(retry_after.is_some()),
+        _ => panic!("a blocked request must be refused"),
+    }
+}
+
+/// Answers every request with an empty stash and the given rate limit state, which grows by
+/// one hit per request.
+fn mock_server(limit: &'static str) -> (String, Arc<AtomicUsize>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server_hits = hits.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let hit = server_hits.fetch_add(1, Ordering::SeqCst) + 1;
+            let body = r#"{"numTabs": 1, "items": []}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\n\
+                 Content-Type: application/json\r\n\
+                 X-Rate-Limit-Rules: Account\r\n\
+                 X-Rate-Limit-Account: {}\r\n\
+                 X-Rate-Limit-Account-State: {}:60:0\r\n\
+                 Content-Length: {}\r\n\
+                 Connection: close\r\n\r\n{}",
+                limit,
+                hit,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+    (base_url, hits)
+}
+
+#[test]
+fn live_source_respects_mock_server_limits() {
+    let (base_url, hits) = mock_server("2:60:60");
+    let account = AccountData {
+        account: "tester".to_owned(),
+        league: "Standard".to_owned(),
+        realm: Realm::Custom {
+            base_url,
+            realm: "pc".to_owned(),
+        },
+        ..Default::default()
+    };
+    let mut source = LiveSource::default();
+
+    assert!(source.fetch(&account, 0).is_ok());
+    assert!(source.fetch(&account, 0).is_ok());
+    match source.fetch(&account, 0) {
+        Err(HelperError::RateLimited { retry_after }) => {
+            assert!(retry_after.unwrap() > Duration::from_secs(50))
+        }
+        other => panic!("expected a refused request, got {:?}", other.map(|_| ())),
+    }
+    assert_eq!(hits.load(Ordering::SeqCst), 2);
+}
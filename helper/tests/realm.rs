@@ -0,0 +1,18 @@
+use helper::Realm;
+
+#[test]
+fn leagues_come_from_the_public_api() {
+    for realm in Realm::ALL.iter() {
+        assert_eq!(
+            realm.league_url(),
+            "http://api.pathofexile.com/leagues",
+            "{}",
+            realm
+        );
+    }
+    let custom = Realm::Custom {
+        base_url: "http://127.0.0.1:8000/".to_owned(),
+        realm: "pc".to_owned(),
+    };
+    assert_eq!(custom.league_url(), "http://127.0.0.1:8000/api/leagues");
+}
@@ -0,0 +1,147 @@
+mod common;
+
+use common::{stash_with_sets, SLOTS};
+use helper::{AccountData, ApiServer, HelperSession};
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Sends `method path` and returns the status code and the JSON body.
+fn request(addr: SocketAddr, method: &str, path: &str) -> (u16, Value) {
+    request_with(
+        addr,
+        method,
+        path,
+        &format!("Host: 127.0.0.1:{}", addr.port()),
+    )
+}
+
+/// Sends `method path` with `headers`, which are separated by `\r\n`.
+fn request_with(addr: SocketAddr, method: &str, path: &str, headers: &str) -> (u16, Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(
+        stream,
+        "{} {} HTTP/1.1\r\n{}\r\n\r\n",
+        method, path, headers
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = &response[response.find("\r\n\r\n").unwrap() + 4..];
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[test]
+fn serves_status_and_sets() {
+    let session = Arc::new(HelperSession::with_source(
+        AccountData::default(),
+        stash_with_sets(2),
+    ));
+    let server = ApiServer::start(session, 0).unwrap();
+    let addr = server.local_addr();
+    assert!(addr.ip().is_loopback());
+
+    let (status, body) = request(addr, "GET", "/status");
+    assert_eq!(status, 200);
+    assert_eq!(body["kind"], "StashStatus");
+    assert_eq!(body["generation"], 0);
+
+    let (status, body) = request(addr, "POST", "/refresh");
+    assert_eq!(status, 200);
+    assert_eq!(
+        (body["sets"].as_u64(), body["generation"].as_u64()),
+        (Some(2), Some(1))
+    );
+
+    let (_, body) = request(addr, "GET", "/sets");
+    assert_eq!(body.as_array().unwrap().len(), 2);
+    assert_eq!(body[0]["kind"], "ChaosRecipe");
+    assert_eq!(body[0]["items"].as_array().unwrap().len(), SLOTS.len());
+
+    let (_, body) = request(addr, "POST", "/sets/next?recipe=chaos");
+    assert_eq!(body["items"][0]["item_type"], "Amulet");
+    // 세트의 아이템이 창고에 있는 동안에는 같은 세트를 보여준다.
+    assert_eq!(request(addr, "POST", "/sets/next").1, body);
+    let (_, body) = request(addr, "GET", "/sets");
+    assert_eq!(body.as_array().unwrap().len(), 2);
+
+    let (_, body) = request(addr, "GET", "/sets?recipe=regal");
+    assert_eq!(body.as_array().unwrap().len(), 0);
+}
+
+#[test]
+fn rejects_unknown_requests() {
+    let session = Arc::new(HelperSession::with_source(
+        AccountData::default(),
+        stash_with_sets(1),
+    ));
+    let server = ApiServer::start(session, 0).unwrap();
+    let addr = server.local_addr();
+
+    assert_eq!(request(addr, "GET", "/nothing").0, 404);
+    assert_eq!(request(addr, "DELETE", "/sets").0, 405);
+    // 상태를 바꾸는 요청은 POST로만 받는다.
+    assert_eq!(request(addr, "GET", "/refresh").0, 405);
+    assert_eq!(request(addr, "GET", "/sets/next").0, 405);
+    assert_eq!(request(addr, "GET", "/status?recipe=%52egal").0, 200);
+    assert_eq!(request(addr, "GET", "/status?recipe=%zz").0, 400);
+    let (status, body) = request(addr, "GET", "/status?recipe=exalted");
+    assert_eq!(status, 400);
+    assert!(body["error"].as_str().unwrap().contains("exalted"));
+}
+
+#[test]
+fn closes_requests_without_an_end() {
+    let session = Arc::new(HelperSession::with_source(
+        AccountData::default(),
+        stash_with_sets(1),
+    ));
+    let server = ApiServer::start(session, 0).unwrap();
+
+    let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    // 줄바꿈 없이 한도보다 긴 헤더
+    write!(
+        stream,
+        "GET /status HTTP/1.1\r\nX-Long: {}",
+        "a".repeat(10_000)
+    )
+    .unwrap();
+    let mut response = Vec::new();
+    // 읽지 않은 데이터가 남은 채로 닫으면 연결이 리셋될 수도 있다.
+    stream.read_to_end(&mut response).ok();
+    assert!(response.is_empty());
+}
+
+#[test]
+fn rejects_other_sites() {
+    let session = Arc::new(HelperSession::with_source(
+        AccountData::default(),
+        stash_with_sets(1),
+    ));
+    let server = ApiServer::start(session, 0).unwrap();
+    let addr = server.local_addr();
+    let port = addr.port();
+
+    let host = |host: &str| request_with(addr, "GET", "/status", &format!("Host: {}", host)).0;
+    assert_eq!(host(&format!("localhost:{}", port)), 200);
+    assert_eq!(host(&format!("127.0.0.1:{}", port)), 200);
+    // DNS 리바인딩
+    assert_eq!(host(&format!("evil.example:{}", port)), 403);
+    assert_eq!(host(&format!("localhost:{}", port + 1)), 403);
+    assert_eq!(host("localhost"), 403);
+    assert_eq!(request_with(addr, "GET", "/status", "X-Nothing: 1").0, 403);
+
+    let origin = |origin: &str| {
+        let headers = format!("Host: 127.0.0.1:{}\r\nOrigin: {}", port, origin);
+        request_with(addr, "POST", "/refresh", &headers).0
+    };
+    assert_eq!(origin("https://evil.example"), 403);
+    assert_eq!(origin("null"), 403);
+    assert_eq!(origin(&format!("http://localhost:{}", port)), 200);
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use common::{items_of_sets, stash_with_sets, LeagueSource, SLOTS, TIMEOUT};
+use helper::{
+    AccountData, HelperSession, MemorySource, RecipeMode, ResponseFromNetwork, StashData,
+    StashEvent, StashTab, TabSelector,
+};
+
+fn set_count(session: &HelperSession) -> usize {
+    match session
+        .refresh_stash_status(RecipeMode::Chaos, TIMEOUT)
+        .unwrap()
+    {
+        ResponseFromNetwork::StashStatus { sets, .. } => sets,
+        other => panic!("expected the stash status, got {:?}", other),
+    }
+}
+
+#[test]
+fn sessions_run_side_by_side() {
+    let first = HelperSession::with_source(AccountData::default(), stash_with_sets(1));
+    let second = HelperSession::with_source(AccountData::default(), stash_with_sets(3));
+    assert_eq!(set_count(&first), 1);
+    assert_eq!(set_count(&second), 3);
+
+    match first.acquire_chaos_list(false, RecipeMode::Chaos).unwrap() {
+        ResponseFromNetwork::ChaosRecipe { items, .. } => assert_eq!(items.len(), SLOTS.len()),
+        other => panic!("expected a set, got {:?}", other),
+    }
+    // 첫 세션이 세트를 꺼내도 다른 세션의 세트는 그대로다.
+    drop(first);
+    match second.acquire_chaos_list(false, RecipeMode::Chaos).unwrap() {
+        ResponseFromNetwork::ChaosRecipe { items, .. } => assert_eq!(items.len(), SLOTS.len()),
+        other => panic!("expected a set, got {:?}", other),
+    }
+}
+
+#[test]
+fn async_requests_can_be_awaited_together() {
+    let session = HelperSession::with_source(AccountData::default(), stash_with_sets(2));
+    let (first, second) = futures::executor::block_on(futures::future::join(
+        session.refresh(RecipeMode::Chaos, TIMEOUT),
+        session.refresh(RecipeMode::Chaos, TIMEOUT),
+    ));
+    for status in [first.unwrap(), second.unwrap()].iter() {
+        match status {
+            ResponseFromNetwork::StashStatus { sets, .. } => assert_eq!(*sets, 2),
+            other => panic!("expected the stash status, got {:?}", other),
+        }
+    }
+    match futures::executor::block_on(session.next_set(RecipeMode::Chaos)).unwrap() {
+        ResponseFromNetwork::ChaosRecipe { items, .. } => assert_eq!(items.len(), SLOTS.len()),
+        other => panic!("expected a set, got {:?}", other),
+    }
+}
+
+#[test]
+fn switching_leagues_consumes_no_set() {
+    let account = AccountData {
+        league: "Ritual".to_owned(),
+        ..Default::default()
+    };
+    let session = HelperSession::with_source(account.clone(), LeagueSource);
+    let events = session.subscribe().unwrap();
+    assert_eq!(set_count(&session), 1);
+    match session
+        .acquire_chaos_list(false, RecipeMode::Chaos)
+        .unwrap()
+    {
+        ResponseFromNetwork::ChaosRecipe { items, .. } => assert_eq!(items.len(), SLOTS.len()),
+        other => panic!("expected a set, got {:?}", other),
+    }
+
+    session.set_account(AccountData {
+        league: "Standard".to_owned(),
+        ..account
+    });
+    assert_eq!(set_count(&session), 0);
+    match session
+        .acquire_chaos_list(false, RecipeMode::Chaos)
+        .unwrap()
+    {
+        ResponseFromNetwork::ChaosRecipe { items, .. } => assert!(items.is_empty()),
+        other => panic!("expected no set, got {:?}", other),
+    }
+    // 이벤트는 가져온 결과에 답하기 전에 보낸다.
+    assert!(!events
+        .try_iter()
+        .any(|event| matches!(event, StashEvent::SetConsumed { .. })));
+}
+
+fn tab(name: &str, index: usize) -> StashTab {
+    StashTab {
+        name: name.to_owned(),
+        index,
+        id: name.to_lowercase(),
+        tab_type: "NormalStash".to_owned(),
+        colour: Default::default(),
+    }
+}
+
+#[test]
+fn sets_name_the_tabs_they_are_in() {
+    let mut items = items_of_sets(1);
+    let rest = items.split_off(4);
+    let source = MemorySource::new(StashData {
+        items,
+        quad_layout: false,
+        tabs: vec![tab("Dump", 0), tab("Chaos", 3)],
+    })
+    .with_tab(
+        3,
+        StashData {
+            items: rest,
+            quad_layout: true,
+            tabs: Vec::new(),
+        },
+    );
+    let account = AccountData {
+        stash_tabs: vec![TabSelector::from(&tab("Dump", 0)), TabSelector::Index(3)],
+        ..Default::default()
+    };
+    let session = HelperSession::with_source(account, source.clone());
+    assert_eq!(set_count(&session), 1);
+    match session
+        .acquire_chaos_list(false, RecipeMode::Chaos)
+        .unwrap()
+    {
+        ResponseFromNetwork::ChaosRecipe {
+            tabs, tab_names, ..
+        } => {
+            assert_eq!(tabs, vec![0, 3]);
+            assert_eq!(tab_names, vec!["Dump", "Chaos"]);
+        }
+        other => panic!("expected a set, got {:?}", other),
+    }
+
+    // 번호로만 고른 탭은 탭 목록을 받지 않으므로 이름을 모른다.
+    let account = AccountData {
+        stash_tabs: vec![TabSelector::Index(0), TabSelector::Index(3)],
+        ..Default::default()
+    };
+    let session = HelperSession::with_source(account, source);
+    assert_eq!(set_count(&session), 1);
+    match session
+        .acquire_chaos_list(false, RecipeMode::Chaos)
+        .unwrap()
+    {
+        ResponseFromNetwork::ChaosRecipe { tab_names, .. } => {
+            assert_eq!(tab_names, vec!["0", "3"])
+        }
+        other => panic!("expected a set, got {:?}", other),
+    }
+}
@@ -0,0 +1,64 @@
+use helper::{simulate, Generator, ItemType, RecipeMode, StashSpec};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+struct Fixture {
+    stash: StashSpec,
+    expect: Vec<Expectation>,
+}
+
+#[derive(Deserialize)]
+struct Expectation {
+    recipe: RecipeMode,
+    generator: Generator,
+    sets: usize,
+    #[serde(default)]
+    unidentified_sets: Option<usize>,
+    #[serde(default)]
+    leftovers: Option<HashMap<ItemType, usize>>,
+}
+
+#[test]
+fn fixtures_build_the_expected_sets() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/simulate");
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let name = path.file_name().unwrap().to_string_lossy().into_owned();
+        let fixture: Fixture =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        let items = fixture.stash.items();
+        for expect in fixture.expect {
+            let report = simulate(&items, expect.recipe.rule(), expect.generator);
+            let case = format!("{} {:?} {:?}", name, expect.recipe, expect.generator);
+            assert_eq!(report.violations, vec![], "{}", case);
+            assert_eq!(report.sets, expect.sets, "{}", case);
+            if let Some(unidentified_sets) = expect.unidentified_sets {
+                assert_eq!(report.unidentified_sets, unidentified_sets, "{}", case);
+            }
+            if let Some(leftovers) = expect.leftovers {
+                assert_eq!(report.leftovers, leftovers, "{}", case);
+            }
+        }
+    }
+}
+
+#[test]
+fn sets_of_another_recipe_are_violations() {
+    let fixture: Fixture =
+        serde_json::from_str(include_str!("fixtures/simulate/two_handed.json")).unwrap();
+    // 카오스 세트는 리갈 레시피를 따르지 않는다.
+    let report = simulate(
+        &fixture.stash.items(),
+        RecipeMode::Regal.rule(),
+        Generator::Optimal,
+    );
+    assert_eq!(report.sets, 3);
+    assert_eq!(report.violations.len(), 3);
+}
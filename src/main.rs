@@ -51,9 +51,12 @@ fn get_item_rect(
 #[derive(Debug)]
 pub enum UIMessage {
     CloseWindow,
-    ShowStatus,
-    ShowStashMask,
+    ShowStatus(helper::RecipeMode),
+    ShowStashMask(helper::RecipeMode),
     ShowResult(helper::ResponseFromNetwork),
+    /// items of the shown set left the stash
+    SetChanged(helper::RecipeMode),
+    ShowError(helper::HelperError),
     ChangeLeftTop,
     ChangeRightBottom,
     InitWindow(ui::WindowRect),
@@ -77,7 +80,10 @@ fn calc_cell_size(stash_w: i64, stash_h: i64) -> (u32, u32) {
 }
 
 fn main() -> Result<()> {
-    helper::init_module();
+    let session = std::sync::Arc::new(helper::HelperSession::new(Default::default()));
+    let ui_session = session.clone();
+    // 창고를 가져오는 동안에도 오버레이가 멈추지 않도록 요청은 다른 스레드에서 기다린다.
+    let pool = futures::executor::ThreadPool::new()?;
 
     let (tx, rx) = std::sync::mpsc::channel::<EventLoopProxy<UIMessage>>();
     std::thread::spawn(move || -> Result<()> {
@@ -141,7 +147,10 @@ fn main() -> Result<()> {
                     }
                 }
                 Event::UserEvent(e) => {
-                    show_window(main_hwnd);
+                    // 세트가 바뀌었거나 오류가 났다고 창을 띄우지는 않는다.
+                    if !matches!(e, UIMessage::SetChanged(_) | UIMessage::ShowError(_)) {
+                        show_window(main_hwnd);
+                    }
                     match e {
                         UIMessage::InitWindow(win_rect) => {
                             win_x = win_rect.left;
@@ -200,26 +209,42 @@ fn main() -> Result<()> {
                         UIMessage::CloseWindow => unsafe {
                             winuser::ShowWindow(main_hwnd, winuser::SW_HIDE);
                         },
-                        UIMessage::ShowStashMask => match helper::acquire_chaos_list(false) {
-                            Ok(result) => {
-                                loop_proxy.send_event(UIMessage::ShowResult(result)).ok();
-                            }
-                            Err(err) => {
-                                ui::error_message_box(err);
-                            }
-                        },
-                        UIMessage::ShowStatus => match helper::acquire_chaos_list(true) {
-                            Ok(result) => {
-                                loop_proxy.send_event(UIMessage::ShowResult(result)).ok();
-                            }
-                            Err(err) => {
-                                ui::error_message_box(err);
-                            }
-                        },
+                        UIMessage::ShowStashMask(mode) => {
+                            let session = session.clone();
+                            let loop_proxy = loop_proxy.clone();
+                            pool.spawn_ok(async move {
+                                show_response(&loop_proxy, session.next_set(mode).await)
+                            });
+                        }
+                        UIMessage::ShowStatus(mode) => {
+                            let session = session.clone();
+                            let loop_proxy = loop_proxy.clone();
+                            pool.spawn_ok(async move {
+                                let status =
+                                    session.refresh(mode, helper::DEFAULT_REFRESH_TIMEOUT).await;
+                                show_response(&loop_proxy, status)
+                            });
+                        }
                         UIMessage::ShowResult(result) => {
                             latest_response = Some(result);
                             main_window.request_redraw();
                         }
+                        UIMessage::SetChanged(mode) => {
+                            // 마스크를 보고 있을 때만 남은 아이템이나 다음 세트로 바꾼다.
+                            let visible = unsafe { winuser::IsWindowVisible(main_hwnd) } != 0;
+                            let showing_set = matches!(
+                                latest_response,
+                                Some(helper::ResponseFromNetwork::ChaosRecipe { .. })
+                            );
+                            if visible && showing_set {
+                                let session = session.clone();
+                                let loop_proxy = loop_proxy.clone();
+                                pool.spawn_ok(async move {
+                                    show_response(&loop_proxy, session.next_set(mode).await)
+                                });
+                            }
+                        }
+                        UIMessage::ShowError(err) => ui::helper_error_message_box(err),
                     }
                 }
                 _ => {}
@@ -229,7 +254,34 @@ fn main() -> Result<()> {
 
     let loop_proxy = rx.recv()?;
 
-    ui::run_ui(loop_proxy)
+    let events = ui_session.subscribe()?;
+    let event_proxy = loop_proxy.clone();
+    std::thread::spawn(move || {
+        for event in events {
+            if let helper::StashEvent::SetConsumed { mode, .. }
+            | helper::StashEvent::SetPartlyPicked { mode, .. } = event
+            {
+                event_proxy.send_event(UIMessage::SetChanged(mode)).ok();
+            }
+        }
+    });
+
+    ui::run_ui(loop_proxy, ui_session)
+}
+
+fn show_response(
+    loop_proxy: &EventLoopProxy<UIMessage>,
+    response: helper::Result<helper::ResponseFromNetwork>,
+) {
+    match response {
+        Ok(result) => {
+            loop_proxy.send_event(UIMessage::ShowResult(result)).ok();
+        }
+        Err(err) => {
+            // 메시지 상자는 이벤트 루프에서 띄운다.
+            loop_proxy.send_event(UIMessage::ShowError(err)).ok();
+        }
+    }
 }
 
 fn toggle_window_transparent(hwnd: *mut HWND__, apply: bool) {
@@ -277,7 +329,17 @@ fn show_window(hwnd: *mut HWND__) {
 fn draw_window(hwnd: *mut HWND__, rect: &mut RECT, data: &helper::ResponseFromNetwork) {
     use std::os::windows::ffi::OsStrExt;
     match data {
-        helper::ResponseFromNetwork::StashStatus((recipe_map, chaos_num)) => {
+        helper::ResponseFromNetwork::StashStatus {
+            recipe_set: recipe_map,
+            sets,
+            unidentified_sets,
+            mode,
+            influenced,
+            unclassified,
+            cooldown,
+            age,
+            ..
+        } => {
             toggle_window_transparent(hwnd, true);
             let types = [
                 helper::ItemType::Weapon1HOrShield,
@@ -299,7 +361,38 @@ fn draw_window(hwnd: *mut HWND__, rect: &mut RECT, data: &helper::ResponseFromNe
                     .unwrap_or((0, 0));
                 info.push(format!("{}: ({}, {})\n", item_type.as_ref(), chaos, regal));
             }
-            info.push(format!("Total Chaos: {}", chaos_num));
+            info.push(format!("Total {}: {}\n", mode, sets));
+            info.push(format!("Unidentified (x2): {}", unidentified_sets));
+            if !influenced.is_empty() {
+                info.push("\n--- Influenced: sets (items) ---");
+            }
+            for summary in influenced.iter() {
+                info.push(format!(
+                    "\n{}: {} ({})",
+                    summary.influence.as_ref(),
+                    summary.sets,
+                    summary.items.values().sum::<usize>()
+                ));
+            }
+            if !unclassified.is_empty() {
+                let mut bases: Vec<&str> = unclassified
+                    .iter()
+                    .map(|item| item.base_type.as_str())
+                    .collect();
+                bases.sort_unstable();
+                bases.dedup();
+                info.push(format!(
+                    "\nUnclassified: {} ({})",
+                    unclassified.len(),
+                    bases.join(", ")
+                ));
+            }
+            if let Some(age) = age {
+                info.push(format!("\nUpdated: {}s ago", age.as_secs()));
+            }
+            if let Some(cooldown) = cooldown {
+                info.push(format!("\nCooldown: {}s", cooldown.as_secs() + 1));
+            }
 
             let text: Vec<_> = info.encode_wide().collect();
             let mut text_rect = rect.clone();
@@ -332,7 +425,14 @@ fn draw_window(hwnd: *mut HWND__, rect: &mut RECT, data: &helper::ResponseFromNe
                 winuser::ReleaseDC(hwnd, main_dc);
             }
         }
-        helper::ResponseFromNetwork::ChaosRecipe((chaos_recipe, is_quad_stash)) => {
+        helper::ResponseFromNetwork::ChaosRecipe {
+            items: chaos_recipe,
+            tabs,
+            tab_names,
+            mode,
+            layout,
+            ..
+        } => {
             let main_dc;
             unsafe {
                 main_dc = winuser::GetDC(hwnd);
@@ -342,9 +442,13 @@ fn draw_window(hwnd: *mut HWND__, rect: &mut RECT, data: &helper::ResponseFromNe
 
             if chaos_recipe.is_empty() {
                 toggle_window_transparent(hwnd, true);
-                let text = OsString::from("카오스 레시피가 없습니다")
-                    .encode_wide()
-                    .collect::<Vec<_>>();
+                let text = OsString::from(match mode {
+                    helper::RecipeMode::Chaos => "카오스 레시피가 없습니다",
+                    helper::RecipeMode::Regal => "리갈 레시피가 없습니다",
+                    helper::RecipeMode::Influenced => "영향력 레시피가 없습니다",
+                })
+                .encode_wide()
+                .collect::<Vec<_>>();
                 unsafe {
                     winuser::DrawTextW(
                         main_dc,
@@ -358,18 +462,71 @@ fn draw_window(hwnd: *mut HWND__, rect: &mut RECT, data: &helper::ResponseFromNe
                 toggle_window_transparent(hwnd, false);
                 unsafe {
                     let brush = wingdi::CreateSolidBrush(RGB(0, 255, 0));
+                    wingdi::SetBkMode(main_dc, wingdi::TRANSPARENT as _);
 
                     for recipe in chaos_recipe.iter() {
                         let (x, y) = (recipe.x as u32, recipe.y as u32);
                         let (w, h) = (recipe.w as u32, recipe.h as u32);
 
                         let (cell_w, cell_h) = calc_cell_size(rect.right as _, rect.bottom as _);
-                        let rect = get_item_rect(x, y, w, h, cell_w, cell_h, *is_quad_stash);
+                        let mut rect = get_item_rect(x, y, w, h, cell_w, cell_h, recipe.quad_tab);
 
                         winuser::FillRect(main_dc, &rect, brush);
+
+                        // 모든 탭의 아이템이 한 격자에 그려지므로, 어느 탭에서 꺼낼지 적어 둔다.
+                        if tabs.len() > 1 {
+                            let name = tabs
+                                .iter()
+                                .position(|tab| *tab == recipe.tab_idx)
+                                .map(|pos| tab_names[pos].as_str())
+                                .unwrap_or_default();
+                            let text = OsString::from(name).encode_wide().collect::<Vec<_>>();
+                            winuser::DrawTextW(
+                                main_dc,
+                                text.as_ptr(),
+                                text.len() as _,
+                                &mut rect,
+                                winuser::DT_CENTER
+                                    | winuser::DT_VCENTER
+                                    | winuser::DT_SINGLELINE
+                                    | winuser::DT_END_ELLIPSIS,
+                            );
+                        }
                     }
+                    wingdi::SetBkMode(main_dc, wingdi::OPAQUE as _);
                     wingdi::DeleteObject(brush as _);
                 }
+
+                // 여러 탭에 걸친 세트라면 들러야 할 탭들을 알려준다.
+                if tabs.len() > 1 {
+                    let text = OsString::from(format!("탭: {}", tab_names.join(", ")))
+                        .encode_wide()
+                        .collect::<Vec<_>>();
+                    unsafe {
+                        winuser::DrawTextW(
+                            main_dc,
+                            text.as_ptr(),
+                            text.len() as _,
+                            rect,
+                            winuser::DT_CENTER | winuser::DT_TOP | winuser::DT_SINGLELINE,
+                        );
+                    }
+                }
+                // 인벤토리에 한 번에 들어가지 않으면 두 번 옮겨야 한다.
+                if layout.is_none() {
+                    let text = OsString::from("인벤토리에 한 번에 들어가지 않습니다")
+                        .encode_wide()
+                        .collect::<Vec<_>>();
+                    unsafe {
+                        winuser::DrawTextW(
+                            main_dc,
+                            text.as_ptr(),
+                            text.len() as _,
+                            rect,
+                            winuser::DT_CENTER | winuser::DT_BOTTOM | winuser::DT_SINGLELINE,
+                        );
+                    }
+                }
             }
             unsafe {
                 winuser::ReleaseDC(hwnd, main_dc);
@@ -1,21 +1,30 @@
 use anyhow::{anyhow, Result};
 use font_loader::system_fonts;
-use helper::AccountData;
+use helper::{AccountData, HelperSession, Realm, RecipeMode, StashTab, TabSelector};
 use iced::{self, widget, Color, Element};
 use iced_native::Event;
-use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::{
     ffi::{OsStr, OsString},
     ptr::null_mut,
+    sync::Arc,
 };
 use winapi;
 
 const SAVE_FILE_NAME: &'static str = "chaos_helper.info";
-lazy_static! {
-    static ref LEAGUE_DATA: Vec<String> = helper::get_league_list()
-        .map_err(|e| error_message_box(e))
-        .unwrap();
+const LEDGER_FILE_NAME: &'static str = "chaos_helper_sales.jsonl";
+
+/// Leagues of `realm`. The request blocks, so it is made on a thread of its own.
+async fn load_league_list(realm: Realm) -> (Realm, helper::Result<Vec<String>>) {
+    let (sender, receiver) = futures::channel::oneshot::channel();
+    let thread_realm = realm.clone();
+    std::thread::spawn(move || {
+        sender.send(helper::get_league_list(&thread_realm)).ok();
+    });
+    let result = receiver
+        .await
+        .unwrap_or(Err(helper::HelperError::Disconnected));
+    (realm, result)
 }
 
 pub fn error_message_box(s: impl ToString) {
@@ -37,13 +46,9 @@ pub fn error_message_box(s: impl ToString) {
     });
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
-struct LeagueIdx(usize);
-
-impl ToString for LeagueIdx {
-    fn to_string(&self) -> String {
-        LEAGUE_DATA[self.0].clone()
-    }
+/// Shows the error together with what the user can do about it.
+pub fn helper_error_message_box(e: helper::HelperError) {
+    error_message_box(format!("{}\n\n{}", e, e.suggestion()));
 }
 
 #[derive(Clone, Debug)]
@@ -51,7 +56,13 @@ enum AppMessage {
     LabelUpdateStarted(usize),
     LabelUpdated { idx: usize, text: String },
     LabelUpdateCompleted(usize),
-    LeagueUpdated(usize),
+    LeagueUpdated(String),
+    RealmUpdated(Realm),
+    LeagueListLoaded(Realm, helper::Result<Vec<String>>),
+    RecipeModeUpdated(RecipeMode),
+    LoadTabList,
+    TabListLoaded(helper::Result<Vec<StashTab>>),
+    TabToggled(usize, bool),
     StartHelper,
     SaveConfig,
     EventOccurred(Event),
@@ -139,15 +150,25 @@ enum AdjustingWindowStatus {
 #[derive(Debug)]
 struct App {
     loop_proxy: crate::EventLoopProxy<crate::UIMessage>,
+    session: Arc<HelperSession>,
     account_data: AccountData,
-    league: Option<usize>,
-    league_picklist_state: widget::pick_list::State<LeagueIdx>,
-    labels: [EditableLabel; 3],
+    league_list: Vec<String>,
+    league_picklist_state: widget::pick_list::State<String>,
+    realm_picklist_state: widget::pick_list::State<Realm>,
+    recipe_mode: RecipeMode,
+    recipe_mode_picklist_state: widget::pick_list::State<RecipeMode>,
+    labels: [EditableLabel; 2],
+    tab_list: Vec<StashTab>,
+    tab_list_button_state: widget::button::State,
+    tab_list_scroll_state: widget::scrollable::State,
     start_button_state: widget::button::State,
     save_button_state: widget::button::State,
     font: iced::Font,
     win_status: AdjustingWindowStatus,
     win_rect: Option<WindowRect>,
+    api_port: Option<u16>,
+    client_log: Option<std::path::PathBuf>,
+    loot_filter: Option<helper::LootFilter>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
@@ -174,6 +195,17 @@ pub struct SaveData {
     #[serde(flatten)]
     account_data: AccountData,
     window_size: Option<WindowRect>,
+    #[serde(default)]
+    recipe_mode: RecipeMode,
+    /// port of the local JSON API, which is off if not set
+    #[serde(default)]
+    api_port: Option<u16>,
+    /// `Client.txt` of the game, which is watched to refresh the stash in hideouts and towns
+    #[serde(default)]
+    client_log: Option<std::path::PathBuf>,
+    /// loot filter written with the counts of the stash, which isn't touched if not set
+    #[serde(default)]
+    loot_filter: Option<helper::LootFilter>,
 }
 
 pub fn save_account_data(path: &std::path::Path, account: &SaveData) -> Result<()> {
@@ -198,7 +230,7 @@ pub fn load_account_data(path: &std::path::Path) -> Result<SaveData> {
 }
 
 impl App {
-    const LABEL_NAMES: [&'static str; 3] = ["Account", "Cookie", "Tab Index"];
+    const LABEL_NAMES: [&'static str; 2] = ["Account", "Cookie"];
 }
 
 use iced::Command;
@@ -209,34 +241,42 @@ impl iced::Application for App {
         SaveData,
         crate::EventLoopProxy<crate::UIMessage>,
         iced::Font,
+        Arc<HelperSession>,
     );
 
     fn new(flag: Self::Flags) -> (Self, Command<Self::Message>) {
-        let league_data = &LEAGUE_DATA;
-        let league = league_data
-            .iter()
-            .enumerate()
-            .find(|(_, league)| flag.0.account_data.league == **league)
-            .map(|(idx, _)| idx);
+        let load_leagues = Command::perform(
+            load_league_list(flag.0.account_data.realm.clone()),
+            |(realm, result)| AppMessage::LeagueListLoaded(realm, result),
+        );
         let labels = [
             EditableLabel::Text(flag.0.account_data.account.clone(), Default::default()),
             EditableLabel::Text(flag.0.account_data.cookie.clone(), Default::default()),
-            EditableLabel::Text(flag.0.account_data.tab_idx.to_string(), Default::default()),
         ];
         (
             Self {
                 loop_proxy: flag.1,
+                session: flag.3,
                 account_data: flag.0.account_data,
-                league,
+                league_list: Vec::new(),
                 league_picklist_state: Default::default(),
+                realm_picklist_state: Default::default(),
+                recipe_mode: flag.0.recipe_mode,
+                recipe_mode_picklist_state: Default::default(),
                 labels,
+                tab_list: Vec::new(),
+                tab_list_button_state: Default::default(),
+                tab_list_scroll_state: Default::default(),
                 start_button_state: Default::default(),
                 save_button_state: Default::default(),
                 font: flag.2,
                 win_status: AdjustingWindowStatus::None,
                 win_rect: flag.0.window_size,
+                api_port: flag.0.api_port,
+                client_log: flag.0.client_log,
+                loot_filter: flag.0.loot_filter,
             },
-            Command::none(),
+            load_leagues,
         )
     }
 
@@ -274,27 +314,64 @@ impl iced::Application for App {
                             self.labels[idx] =
                                 EditableLabel::Text(text.clone(), Default::default());
                         }
-                        2 => {
-                            if let Ok(tab_idx) = text.parse::<usize>() {
-                                self.account_data.tab_idx = tab_idx;
-                            }
-                            self.labels[idx] = EditableLabel::Text(
-                                self.account_data.tab_idx.to_string(),
-                                Default::default(),
-                            );
-                        }
                         _ => unreachable!(),
                     }
                 }
             }
-            AppMessage::LeagueUpdated(idx) => {
-                self.league = Some(idx);
-                self.account_data.league = LEAGUE_DATA[idx].clone();
+            AppMessage::LeagueUpdated(league) => {
+                self.account_data.league = league;
+            }
+            AppMessage::RealmUpdated(realm) => {
+                if realm != self.account_data.realm {
+                    // 서버마다 리그와 창고 탭이 다르다.
+                    self.league_list.clear();
+                    self.tab_list.clear();
+                    self.account_data.realm = realm.clone();
+                    return Command::perform(load_league_list(realm), |(realm, result)| {
+                        AppMessage::LeagueListLoaded(realm, result)
+                    });
+                }
+            }
+            AppMessage::LeagueListLoaded(realm, result) => {
+                // 그사이 서버를 또 바꿨으면 늦게 온 목록은 버린다.
+                if realm == self.account_data.realm {
+                    match result {
+                        Ok(league_list) => self.league_list = league_list,
+                        Err(e) => helper_error_message_box(e),
+                    }
+                }
+            }
+            AppMessage::RecipeModeUpdated(mode) => {
+                self.recipe_mode = mode;
+            }
+            AppMessage::LoadTabList => {
+                let session = self.session.clone();
+                let account_data = self.account_data.clone();
+                return Command::perform(
+                    async move { session.tab_list(&account_data).await },
+                    AppMessage::TabListLoaded,
+                );
+            }
+            AppMessage::TabListLoaded(result) => match result {
+                Ok(tab_list) => self.tab_list = tab_list,
+                Err(e) => helper_error_message_box(e),
+            },
+            AppMessage::TabToggled(idx, checked) => {
+                let tab = &self.tab_list[idx];
+                self.account_data
+                    .stash_tabs
+                    .retain(|selector| !selector.is_tab(tab));
+                if checked {
+                    self.account_data.stash_tabs.push(TabSelector::from(tab));
+                }
             }
             AppMessage::StartHelper => {
-                helper::set_account(self.account_data.clone());
+                self.session.set_account(self.account_data.clone());
                 crate::IS_INITIALIZED.store(true, std::sync::atomic::Ordering::Relaxed);
-                if let Err(e) = self.loop_proxy.send_event(crate::UIMessage::ShowStatus) {
+                if let Err(e) = self
+                    .loop_proxy
+                    .send_event(crate::UIMessage::ShowStatus(self.recipe_mode))
+                {
                     error_message_box(e);
                 }
             }
@@ -308,6 +385,10 @@ impl iced::Application for App {
                 let save_data = SaveData {
                     account_data: self.account_data.clone(),
                     window_size: self.win_rect,
+                    recipe_mode: self.recipe_mode,
+                    api_port: self.api_port,
+                    client_log: self.client_log.clone(),
+                    loot_filter: self.loot_filter.clone(),
                 };
                 if let Err(e) = save_account_data(&save_name, &save_data) {
                     error_message_box(e);
@@ -346,14 +427,17 @@ impl iced::Application for App {
                             self.win_status = AdjustingWindowStatus::LeftTop;
                         }
                         KeyCode::F9 => {
-                            if let Err(e) =
-                                self.loop_proxy.send_event(crate::UIMessage::ShowStashMask)
+                            if let Err(e) = self
+                                .loop_proxy
+                                .send_event(crate::UIMessage::ShowStashMask(self.recipe_mode))
                             {
                                 error_message_box(e);
                             }
                         }
                         KeyCode::F10 => {
-                            if let Err(e) = self.loop_proxy.send_event(crate::UIMessage::ShowStatus)
+                            if let Err(e) = self
+                                .loop_proxy
+                                .send_event(crate::UIMessage::ShowStatus(self.recipe_mode))
                             {
                                 error_message_box(e);
                             }
@@ -394,6 +478,22 @@ impl iced::Application for App {
 
         let font = self.font;
 
+        let realm_row = Row::new()
+            .padding(20)
+            .spacing(20)
+            .align_items(Align::Center)
+            .width(Length::Fill)
+            .push(Text::new("Realm").font(font))
+            .push(
+                PickList::new(
+                    &mut self.realm_picklist_state,
+                    &Realm::ALL[..],
+                    Some(self.account_data.realm.clone()),
+                    AppMessage::RealmUpdated,
+                )
+                .width(Length::Fill),
+            );
+
         let radio_row = Row::new()
             .padding(20)
             .spacing(20)
@@ -401,21 +501,39 @@ impl iced::Application for App {
             .width(Length::Fill)
             .push(Text::new("League").font(font));
 
-        let league_data = &LEAGUE_DATA;
-        let selected_league = self.league.map(|selected_idx| LeagueIdx(selected_idx));
-        let league_indices: Vec<_> = (0..league_data.len()).map(|idx| LeagueIdx(idx)).collect();
+        let selected_league = self
+            .league_list
+            .iter()
+            .find(|league| **league == self.account_data.league)
+            .cloned();
         let radio_row = radio_row.push(
             PickList::new(
                 &mut self.league_picklist_state,
-                league_indices,
+                &self.league_list[..],
                 selected_league,
-                |idx| AppMessage::LeagueUpdated(idx.0),
+                AppMessage::LeagueUpdated,
             )
             .width(Length::Fill),
         );
 
+        let recipe_row = Row::new()
+            .padding(20)
+            .spacing(20)
+            .align_items(Align::Center)
+            .width(Length::Fill)
+            .push(Text::new("Recipe").font(font))
+            .push(
+                PickList::new(
+                    &mut self.recipe_mode_picklist_state,
+                    &RecipeMode::ALL[..],
+                    Some(self.recipe_mode),
+                    AppMessage::RecipeModeUpdated,
+                )
+                .width(Length::Fill),
+            );
+
         let column = Column::new().spacing(20).align_items(Align::Center);
-        let column = column.push(radio_row);
+        let column = column.push(realm_row).push(radio_row).push(recipe_row);
         let column = self
             .labels
             .iter_mut()
@@ -427,6 +545,58 @@ impl iced::Application for App {
                 col.push(row)
             });
 
+        let selected_tabs: Vec<_> = self
+            .account_data
+            .stash_tabs
+            .iter()
+            .map(|selector| selector.to_string())
+            .collect();
+        let tab_row = Row::new()
+            .padding(20)
+            .spacing(20)
+            .align_items(Align::Center)
+            .width(Length::Fill)
+            .push(Text::new("Stash Tabs").font(font))
+            .push(
+                Container::new(Text::new(selected_tabs.join(", ")).font(font))
+                    .padding(4)
+                    .width(Length::Fill)
+                    .style(Bordered),
+            )
+            .push(
+                Button::new(
+                    &mut self.tab_list_button_state,
+                    Text::new("탭 목록 불러오기").font(font),
+                )
+                .on_press(AppMessage::LoadTabList),
+            );
+        let column = column.push(tab_row);
+        let column = if self.tab_list.is_empty() {
+            column
+        } else {
+            let stash_tabs = &self.account_data.stash_tabs;
+            let tab_checkboxes = self.tab_list.iter().enumerate().fold(
+                Scrollable::new(&mut self.tab_list_scroll_state)
+                    .spacing(10)
+                    .max_height(200)
+                    .width(Length::Fill),
+                |scroll, (idx, tab)| {
+                    let checked = stash_tabs.iter().any(|selector| selector.is_tab(tab));
+                    // 탭 이름이 한글일 수 있어서 글꼴을 지정할 수 있는 Text를 따로 붙인다.
+                    scroll.push(
+                        Row::new()
+                            .spacing(10)
+                            .align_items(Align::Center)
+                            .push(Checkbox::new(checked, "", move |checked| {
+                                AppMessage::TabToggled(idx, checked)
+                            }))
+                            .push(Text::new(tab.to_string()).font(font)),
+                    )
+                },
+            );
+            column.push(Row::new().padding(20).push(tab_checkboxes))
+        };
+
         column
             .push(
                 Container::new(
@@ -454,19 +624,56 @@ impl iced::Application for App {
     }
 }
 
-pub fn run_ui(loop_proxy: crate::EventLoopProxy<crate::UIMessage>) -> Result<()> {
+pub fn run_ui(
+    loop_proxy: crate::EventLoopProxy<crate::UIMessage>,
+    session: Arc<HelperSession>,
+) -> Result<()> {
     use iced::Application;
 
-    let save_name = dirs::home_dir()
-        .unwrap_or_else(|| {
-            error_message_box("사용자 폴더의 위치를 불러올 수 없습니다.");
-            panic!("사용자 폴더의 위치를 불러올 수 없습니다.")
-        })
-        .join(SAVE_FILE_NAME);
-    let save_data = load_account_data(&save_name)
+    let home = dirs::home_dir().unwrap_or_else(|| {
+        error_message_box("사용자 폴더의 위치를 불러올 수 없습니다.");
+        panic!("사용자 폴더의 위치를 불러올 수 없습니다.")
+    });
+    let save_data = load_account_data(&home.join(SAVE_FILE_NAME))
         .map_err(|e| error_message_box(e))
         .unwrap_or_default();
 
+    // 판 세트는 세션이 끝날 때까지 기록한다.
+    if let Err(e) = helper::Ledger::new(home.join(LEDGER_FILE_NAME)).record(&session) {
+        error_message_box(e);
+    }
+
+    // 창이 닫힐 때까지 서버를 살려 둔다.
+    let _api_server = match save_data.api_port {
+        Some(port) => match helper::ApiServer::start(session.clone(), port) {
+            Ok(server) => Some(server),
+            Err(e) => {
+                error_message_box(e);
+                None
+            }
+        },
+        None => None,
+    };
+    let _log_watcher = match &save_data.client_log {
+        Some(path) => match helper::LogWatcher::start(
+            path,
+            session.clone(),
+            helper::DEFAULT_LOG_POLL_INTERVAL,
+        ) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                error_message_box(e);
+                None
+            }
+        },
+        None => None,
+    };
+    if let Some(filter) = &save_data.loot_filter {
+        if let Err(e) = filter.keep_updated(&session) {
+            error_message_box(e);
+        }
+    }
+
     loop_proxy
         .send_event(crate::UIMessage::InitWindow(
             save_data.window_size.unwrap_or_default(),
@@ -487,6 +694,9 @@ pub fn run_ui(loop_proxy: crate::EventLoopProxy<crate::UIMessage>) -> Result<()>
         iced::Font::Default
     };
 
-    App::run(iced::Settings::with_flags((save_data, loop_proxy, font))).unwrap();
+    App::run(iced::Settings::with_flags((
+        save_data, loop_proxy, font, session,
+    )))
+    .unwrap();
     Ok(())
 }